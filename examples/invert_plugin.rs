@@ -0,0 +1,77 @@
+//! Example byteproc plugin: XOR every byte with a configurable mask
+//! (default 0xff, i.e. invert all bits).
+//!
+//! Build with `cargo build --release --example invert_plugin`, copy the
+//! library from `target/release/examples/` into your `plugin_dir`, then
+//! reference it as `"pipeline": ["invert"]`.
+
+use byteproc::processor::{PLUGIN_ABI_VERSION, PluginBuffer, PluginDescriptor};
+use std::ffi::{c_char, c_void};
+
+struct Invert {
+    mask: u8,
+}
+
+fn parse_config(config: &[u8]) -> Result<Invert, String> {
+    let value: serde_json::Value = serde_json::from_slice(config).map_err(|e| e.to_string())?;
+    let mask = match value.get("mask") {
+        None => 0xff,
+        Some(m) => m
+            .as_u64()
+            .and_then(|m| u8::try_from(m).ok())
+            .ok_or_else(|| format!("mask must be 0-255, got {}", m))?,
+    };
+    Ok(Invert { mask })
+}
+
+unsafe extern "C" fn create(config: *const u8, config_len: usize, error: *mut PluginBuffer) -> *mut c_void {
+    let config = unsafe { std::slice::from_raw_parts(config, config_len) };
+    match parse_config(config) {
+        Ok(state) => Box::into_raw(Box::new(state)) as *mut c_void,
+        Err(msg) => {
+            unsafe { *error = PluginBuffer::from_vec(msg.into_bytes()) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn process(
+    instance: *mut c_void,
+    input: *const u8,
+    input_len: usize,
+    output: *mut PluginBuffer,
+) -> i32 {
+    let state = unsafe { &*(instance as *const Invert) };
+    let input = unsafe { std::slice::from_raw_parts(input, input_len) };
+    let result: Vec<u8> = input.iter().map(|b| b ^ state.mask).collect();
+    unsafe { *output = PluginBuffer::from_vec(result) };
+    0
+}
+
+unsafe extern "C" fn free_buffer(buffer: PluginBuffer) {
+    drop(unsafe { buffer.into_vec() });
+}
+
+unsafe extern "C" fn destroy(instance: *mut c_void) {
+    drop(unsafe { Box::from_raw(instance as *mut Invert) });
+}
+
+struct Descriptor(PluginDescriptor);
+// SAFETY: the descriptor only holds pointers to static data and functions
+unsafe impl Sync for Descriptor {}
+
+static DESCRIPTOR: Descriptor = Descriptor(PluginDescriptor {
+    abi_version: PLUGIN_ABI_VERSION,
+    name: c"invert".as_ptr() as *const c_char,
+    config_schema: c"{\"type\": \"object\", \"properties\": {\"mask\": {\"type\": \"integer\"}}}".as_ptr()
+        as *const c_char,
+    create,
+    process,
+    free_buffer,
+    destroy,
+});
+
+#[unsafe(no_mangle)]
+pub extern "C" fn byteproc_plugin() -> *const PluginDescriptor {
+    &DESCRIPTOR.0
+}
@@ -1,32 +1,39 @@
 // src/lib.rs
 
 pub mod processor {
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use hex::FromHex;
-use log::{ info,error, LevelFilter};
-use serde::Deserialize;
-use simplelog::{ConfigBuilder, WriteLogger};
+use log::{ info,error,warn, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use simplelog::{CombinedLogger, ConfigBuilder, SharedLogger, WriteLogger};
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::BinaryHeap,
     error::Error,
     fmt,
     fs::{File, OpenOptions},
-    io::{self, Read},
-    path::PathBuf,
+    io::{self, Read, Write},
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    ffi::{c_char, c_void, CStr},
+    path::{Path, PathBuf},
+    rc::Rc,
     str::FromStr,
+    time::{Duration, Instant},
 };
 use base64::Engine;
 use zeroize::Zeroize;
 use zmq::{Context, Socket};
-use std::sync::OnceLock;
+use std::sync::{atomic::{AtomicBool, Ordering}, Mutex, OnceLock};
 
 // -------------- Enums and Constants --------------
 
 /// Input types
-#[derive(Parser, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum InputType {
     Stdin,
+    #[serde(alias = "zmq_pull")]
     ZmqPull,
 }
 
@@ -58,11 +65,14 @@ impl FromStr for InputType {
 }
 
 /// Output types
-#[derive(Parser, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputType {
     Stdout,
+    #[serde(alias = "zmq_push")]
     ZmqPush,
+    /// Append one hex line per message to a file
+    File,
 }
 
 impl Default for OutputType {
@@ -76,6 +86,7 @@ impl std::fmt::Display for OutputType {
         match self {
             OutputType::Stdout => write!(f, "stdout"),
             OutputType::ZmqPush => write!(f, "zmq_push"),
+            OutputType::File => write!(f, "file"),
         }
     }
 }
@@ -87,13 +98,14 @@ impl FromStr for OutputType {
         match s.to_lowercase().as_str() {
             "stdout" => Ok(OutputType::Stdout),
             "zmq_push" => Ok(OutputType::ZmqPush),
+            "file" => Ok(OutputType::File),
             _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid output type: {}", s))),
         }
     }
 }
 
 /// Base64 modes
-#[derive(Parser, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Base64Mode {
     Encode,
@@ -127,518 +139,6487 @@ impl FromStr for Base64Mode {
     }
 }
 
-// Module name constants
-pub const MODULE_PASSTHROUGH: &str = "passthrough";
-pub const MODULE_XOR: &str = "xor";
-pub const MODULE_BASE64: &str = "base64";
-
-// -------------- Error type --------------
-
-#[derive(Debug)]
-pub enum ByteProcError {
-    Io(String),
-    InvalidConfiguration(String),
-    HexDecode(String),
-    MaxSizeExceeded(usize, usize),
-    Zmq(String),
-    Module(String),
+/// Line coding schemes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LineCodeScheme {
+    /// IEEE 802.3 Manchester: 0 = high-to-low, 1 = low-to-high
+    #[default]
+    ManchesterIeee,
+    /// G.E. Thomas Manchester: 0 = low-to-high, 1 = high-to-low
+    ManchesterThomas,
+    /// Differential Manchester: always a mid-bit transition, 0 = extra transition at bit start
+    DiffManchester,
+    /// NRZI (HDLC/USB convention): 0 = level change, 1 = no change
+    Nrzi,
+    /// Differential encoding: out = in XOR previous out
+    Differential,
 }
 
-impl fmt::Display for ByteProcError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl std::fmt::Display for LineCodeScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ByteProcError::Io(e) => write!(f, "I/O error: {}", e),
-            ByteProcError::InvalidConfiguration(e) => write!(f, "Invalid configuration: {}", e),
-            ByteProcError::HexDecode(e) => write!(f, "Hex decode error: {}", e),
-            ByteProcError::MaxSizeExceeded(max, got) => {
-                write!(f, "Stream too large: max {} bytes, got {}", max, got)
-            }
-            ByteProcError::Zmq(e) => write!(f, "ZeroMQ error: {}", e),
-            ByteProcError::Module(e) => write!(f, "Module processing error: {}", e),
+            LineCodeScheme::ManchesterIeee => write!(f, "manchester_ieee"),
+            LineCodeScheme::ManchesterThomas => write!(f, "manchester_thomas"),
+            LineCodeScheme::DiffManchester => write!(f, "diff_manchester"),
+            LineCodeScheme::Nrzi => write!(f, "nrzi"),
+            LineCodeScheme::Differential => write!(f, "differential"),
         }
     }
 }
 
-impl Error for ByteProcError {}
+impl FromStr for LineCodeScheme {
+    type Err = ByteProcError;
 
-// -------------- ByteProcessor trait --------------
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "manchester_ieee" => Ok(LineCodeScheme::ManchesterIeee),
+            "manchester_thomas" => Ok(LineCodeScheme::ManchesterThomas),
+            "diff_manchester" => Ok(LineCodeScheme::DiffManchester),
+            "nrzi" => Ok(LineCodeScheme::Nrzi),
+            "differential" => Ok(LineCodeScheme::Differential),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid line coding scheme: {}", s))),
+        }
+    }
+}
 
-pub trait ByteProcessor {
-    fn name(&self) -> &'static str;
-    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError>;
+/// Line coding modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineCodeMode {
+    #[default]
+    Encode,
+    Decode,
 }
 
-// -------------- Modules --------------
+impl std::fmt::Display for LineCodeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineCodeMode::Encode => write!(f, "encode"),
+            LineCodeMode::Decode => write!(f, "decode"),
+        }
+    }
+}
 
-/// Passthrough
-#[derive(Debug)]
-pub struct Passthrough;
-impl ByteProcessor for Passthrough {
-    fn name(&self) -> &'static str { MODULE_PASSTHROUGH }
-    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
-        Ok(input.to_vec())
+impl FromStr for LineCodeMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "encode" => Ok(LineCodeMode::Encode),
+            "decode" => Ok(LineCodeMode::Decode),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid line coding mode: {}", s))),
+        }
     }
 }
 
-/// XOR
-#[derive(Debug)]
-pub struct XorModule {
-    key: XorKey,
+/// Bit order within a byte
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BitOrder {
+    #[default]
+    Msb,
+    Lsb,
 }
-#[derive(Debug)]
-struct XorKey { key: Vec<u8> }
-impl Drop for XorKey { fn drop(&mut self) { self.key.zeroize(); } }
-impl XorModule {
-    pub fn new(hex_key: &str, pad_byte: Option<u8>) -> Result<Self, ByteProcError> {
-        let raw = Vec::from_hex(hex_key)
-            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
-        if raw.is_empty() {
-            return Err(ByteProcError::InvalidConfiguration("xor_key cannot be empty".into()));
+
+impl std::fmt::Display for BitOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitOrder::Msb => write!(f, "msb"),
+            BitOrder::Lsb => write!(f, "lsb"),
         }
-        // pad or cycle?
-        let _pad = pad_byte.unwrap_or(0);
-        // Note: we'll cycle if pad_byte is None; no further action here
-        Ok(XorModule {
-            key: XorKey { key: raw },
-        })
     }
 }
-impl ByteProcessor for XorModule {
-    fn name(&self) -> &'static str { MODULE_XOR }
-    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
-        let mut out = Vec::with_capacity(input.len());
-        let key = &self.key.key;
-        for (i, &b) in input.iter().enumerate() {
-            let k = key[i % key.len()];
-            out.push(b ^ k);
+
+impl FromStr for BitOrder {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "msb" => Ok(BitOrder::Msb),
+            "lsb" => Ok(BitOrder::Lsb),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid bit order: {}", s))),
         }
-        Ok(out)
     }
 }
 
-/// Base64
-#[derive(Debug)]
-pub struct Base64Module {
-    encode: bool,
-    padding: bool,
+/// Bit pack/unpack modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BitsMode {
+    #[default]
+    Unpack,
+    Pack,
 }
-impl Base64Module {
-    pub fn new(encode: bool, padding: bool) -> Self {
-        Base64Module { encode, padding }
+
+impl std::fmt::Display for BitsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitsMode::Unpack => write!(f, "unpack"),
+            BitsMode::Pack => write!(f, "pack"),
+        }
     }
 }
-impl ByteProcessor for Base64Module {
-    fn name(&self) -> &'static str { MODULE_BASE64 }
-    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
-        if self.encode {
-            let cfg = if self.padding {
-                base64::engine::general_purpose::STANDARD
-            } else {
-                base64::engine::general_purpose::STANDARD_NO_PAD
-            };
-            Ok(cfg.encode(input).into_bytes())
-        } else {
-            let cfg = if self.padding {
-                base64::engine::general_purpose::STANDARD
-            } else {
-                base64::engine::general_purpose::STANDARD_NO_PAD
-            };
-            cfg.decode(input).map_err(|e| ByteProcError::Module(e.to_string()))
-            }
+
+impl FromStr for BitsMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unpack" => Ok(BitsMode::Unpack),
+            "pack" => Ok(BitsMode::Pack),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid bits mode: {}", s))),
+        }
     }
 }
 
-// -------------- Config structures --------------
+/// Byte order of multi-byte header fields
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
 
-#[derive(Parser, Deserialize, Debug, Clone)]
-#[command(name = "byteproc")]
-pub struct Config {
-    /// Path to config file
-    #[arg(long)]
-    #[serde(skip)]
-    pub config: Option<PathBuf>,
+impl std::fmt::Display for Endianness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endianness::Big => write!(f, "big"),
+            Endianness::Little => write!(f, "little"),
+        }
+    }
+}
 
-    /// Maximum stream size in KB
-    #[arg(long, default_value_t = 64)]
-    #[serde(default = "default_max_stream_size_kb")]
-    pub max_stream_size_kb: usize,
+impl FromStr for Endianness {
+    type Err = ByteProcError;
 
-    // Input/Output options
-    #[arg(long, default_value = "stdin")]
-    #[serde(default)]
-    pub input_type: InputType,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "big" => Ok(Endianness::Big),
+            "little" => Ok(Endianness::Little),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid endianness: {}", s))),
+        }
+    }
+}
 
-    #[arg(long)]
-    #[serde(default)]
-    pub input_zmq_socket: Option<String>,
+/// Frame check sequence appended by the framer
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameCrc {
+    #[default]
+    None,
+    /// CRC-16/CCITT-FALSE (poly 0x1021, init 0xffff)
+    Crc16,
+    /// CRC-32 as used by Ethernet and zlib
+    Crc32,
+}
 
-    #[arg(long, default_value_t = false)]
-    #[serde(default)]
-    pub input_zmq_bind: bool,
+impl std::fmt::Display for FrameCrc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameCrc::None => write!(f, "none"),
+            FrameCrc::Crc16 => write!(f, "crc16"),
+            FrameCrc::Crc32 => write!(f, "crc32"),
+        }
+    }
+}
 
-    #[arg(long, default_value = "stdout")]
-    #[serde(default)]
-    pub output_type: OutputType,
+impl FromStr for FrameCrc {
+    type Err = ByteProcError;
 
-    #[arg(long)]
-    #[serde(default)]
-    pub output_zmq_socket: Option<String>,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(FrameCrc::None),
+            "crc16" => Ok(FrameCrc::Crc16),
+            "crc32" => Ok(FrameCrc::Crc32),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid frame CRC: {}", s))),
+        }
+    }
+}
 
-    #[arg(long, default_value_t = false)]
-    #[serde(default)]
-    pub output_zmq_bind: bool,
+/// Granularity of the deframer sync word search
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameSearch {
+    /// Sync word is byte aligned
+    #[default]
+    Byte,
+    /// Sync word may start at any bit offset
+    Bit,
+}
 
-    // ZMQ options
-    #[arg(long, default_value_t = 1000)]
-    #[serde(default = "default_zmq_reconnect_interval_ms")]
-    pub zmq_reconnect_interval_ms: u32,
+impl std::fmt::Display for FrameSearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameSearch::Byte => write!(f, "byte"),
+            FrameSearch::Bit => write!(f, "bit"),
+        }
+    }
+}
 
-    #[arg(long, default_value_t = 5)]
-    #[serde(default = "default_zmq_max_reconnect_attempts")]
-    pub zmq_max_reconnect_attempts: u32,
+impl FromStr for FrameSearch {
+    type Err = ByteProcError;
 
-    #[arg(long, default_value_t = 5000)]
-    #[serde(default = "default_zmq_send_timeout_ms")]
-    pub zmq_send_timeout_ms: i32,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "byte" => Ok(FrameSearch::Byte),
+            "bit" => Ok(FrameSearch::Bit),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid frame search: {}", s))),
+        }
+    }
+}
 
-    #[arg(long, default_value_t = 5000)]
-    #[serde(default = "default_zmq_receive_timeout_ms")]
-    pub zmq_receive_timeout_ms: i32,
+/// Channel error models
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelModel {
+    /// Binary symmetric channel: independent bit flips at `channel_ber`
+    #[default]
+    Bsc,
+    /// Two-state Gilbert-Elliott burst channel
+    GilbertElliott,
+}
 
-    #[arg(long, default_value_t = 0)]
-    #[serde(default = "default_zmq_linger_ms")]
-    pub zmq_linger_ms: i32,
+impl std::fmt::Display for ChannelModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelModel::Bsc => write!(f, "bsc"),
+            ChannelModel::GilbertElliott => write!(f, "gilbert_elliott"),
+        }
+    }
+}
 
-    // Logging options
-    #[arg(long, default_value_t = true)]
-    #[serde(default = "default_log_enabled")]
-    pub log_enabled: bool,
+impl FromStr for ChannelModel {
+    type Err = ByteProcError;
 
-    #[arg(long, default_value = "info")]
-    #[serde(default = "default_log_level")]
-    pub log_level: String,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bsc" => Ok(ChannelModel::Bsc),
+            "gilbert_elliott" => Ok(ChannelModel::GilbertElliott),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid channel model: {}", s))),
+        }
+    }
+}
 
-    #[arg(long, default_value = "byteproc.log")]
-    #[serde(default = "default_log_file")]
-    pub log_file: String,
+/// Padding modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PadMode {
+    #[default]
+    Pad,
+    Unpad,
+}
 
-    #[arg(long, default_value_t = true)]
-    #[serde(default = "default_log_append")]
-    pub log_append: bool,
+impl std::fmt::Display for PadMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PadMode::Pad => write!(f, "pad"),
+            PadMode::Unpad => write!(f, "unpad"),
+        }
+    }
+}
 
-    // Processing modules
-    #[arg(long, default_value_t = false)]
-    #[serde(default)]
-    pub xor_enabled: bool,
+impl FromStr for PadMode {
+    type Err = ByteProcError;
 
-    #[arg(long)]
-    #[serde(default)]
-    pub xor_key: Option<String>,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pad" => Ok(PadMode::Pad),
+            "unpad" => Ok(PadMode::Unpad),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid pad mode: {}", s))),
+        }
+    }
+}
 
-    #[arg(long, default_value = "00")]
-    #[serde(default = "default_xor_pad")]
-    pub xor_pad: String,
+/// Block padding schemes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PadScheme {
+    /// PKCS#7: n bytes of value n, always at least one byte
+    #[default]
+    Pkcs7,
+    /// Zero bytes up to the block boundary, unpad strips all trailing zeros
+    Zero,
+    /// ISO/IEC 7816-4: 0x80 followed by zero bytes
+    Iso7816,
+}
 
-    #[arg(long, default_value_t = false)]
-    #[serde(default)]
-    pub base64_enabled: bool,
+impl std::fmt::Display for PadScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PadScheme::Pkcs7 => write!(f, "pkcs7"),
+            PadScheme::Zero => write!(f, "zero"),
+            PadScheme::Iso7816 => write!(f, "iso7816"),
+        }
+    }
+}
 
-    #[arg(long, default_value = "encode")]
-    #[serde(default = "default_base64_mode")]
-    pub base64_mode: Base64Mode,
+impl FromStr for PadScheme {
+    type Err = ByteProcError;
 
-    #[arg(long, default_value_t = true)]
-    #[serde(default = "default_base64_padding")]
-    pub base64_padding: bool,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pkcs7" => Ok(PadScheme::Pkcs7),
+            "zero" => Ok(PadScheme::Zero),
+            "iso7816" => Ok(PadScheme::Iso7816),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid pad scheme: {}", s))),
+        }
+    }
 }
 
-// Default function implementations
-fn default_max_stream_size_kb() -> usize { 64 }
-fn default_input_type() -> InputType { InputType::Stdin }
-fn default_output_type() -> OutputType { OutputType::Stdout }
-fn default_zmq_reconnect_interval_ms() -> u32 { 1000 }
-fn default_zmq_max_reconnect_attempts() -> u32 { 5 }
-fn default_zmq_send_timeout_ms() -> i32 { 5000 }
-fn default_zmq_receive_timeout_ms() -> i32 { 5000 }
-fn default_zmq_linger_ms() -> i32 { 3000 }
-fn default_log_enabled() -> bool { true }
-fn default_log_level() -> String { "info".into() }
-fn default_log_file() -> String { "byteproc.log".into() }
-fn default_log_append() -> bool { true }
-fn default_xor_pad() -> String { "00".into() }
-fn default_base64_mode() -> Base64Mode { Base64Mode::Encode }
-fn default_base64_padding() -> bool { true }
+/// Bitwise operations
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BitOp {
+    /// AND with the cycling mask
+    And,
+    /// OR with the cycling mask
+    Or,
+    /// Invert every bit
+    #[default]
+    Not,
+    /// Rotate left
+    Rotl,
+    /// Rotate right
+    Rotr,
+    /// Shift left, zero fill
+    Shl,
+    /// Shift right, zero fill
+    Shr,
+}
 
-// Implement the Default trait for Config
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            config: None,
-            max_stream_size_kb: default_max_stream_size_kb(),
-            input_type: default_input_type(),
-            input_zmq_socket: None,
-            input_zmq_bind: false, // Default for bool
-            output_type: default_output_type(),
-            output_zmq_socket: None,
-            output_zmq_bind: false, // Default for bool
-            zmq_reconnect_interval_ms: default_zmq_reconnect_interval_ms(),
-            zmq_max_reconnect_attempts: default_zmq_max_reconnect_attempts(),
-            zmq_send_timeout_ms: default_zmq_send_timeout_ms(),
-            zmq_receive_timeout_ms: default_zmq_receive_timeout_ms(),
-            zmq_linger_ms: default_zmq_linger_ms(),
-            log_enabled: default_log_enabled(),
-            log_level: default_log_level(),
-            log_file: default_log_file(),
-            log_append: default_log_append(),
-            xor_enabled: false, // Default for bool
-            xor_key: None,
-            xor_pad: default_xor_pad(),
-            base64_enabled: false, // Default for bool
-            base64_mode: default_base64_mode(),
-            base64_padding: default_base64_padding(),
+impl std::fmt::Display for BitOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitOp::And => write!(f, "and"),
+            BitOp::Or => write!(f, "or"),
+            BitOp::Not => write!(f, "not"),
+            BitOp::Rotl => write!(f, "rotl"),
+            BitOp::Rotr => write!(f, "rotr"),
+            BitOp::Shl => write!(f, "shl"),
+            BitOp::Shr => write!(f, "shr"),
         }
     }
 }
 
-impl Config {
-    /// Calculated field: Maximum stream size in bytes
-    pub fn max_stream_size(&self) -> Result<usize, ByteProcError> {
-        self.max_stream_size_kb
-            .checked_mul(1024)
-            .ok_or_else(|| ByteProcError::InvalidConfiguration("max_stream_size_kb too large".into()))
+impl FromStr for BitOp {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "and" => Ok(BitOp::And),
+            "or" => Ok(BitOp::Or),
+            "not" => Ok(BitOp::Not),
+            "rotl" => Ok(BitOp::Rotl),
+            "rotr" => Ok(BitOp::Rotr),
+            "shl" => Ok(BitOp::Shl),
+            "shr" => Ok(BitOp::Shr),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid bitwise operation: {}", s))),
+        }
     }
-    
-    /// Calculated field: Base64 encode mode
-    pub fn base64_encode(&self) -> bool {
-        self.base64_mode == Base64Mode::Encode
+}
+
+/// Whether shifts and rotations act on each byte or on the whole buffer
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BitScope {
+    #[default]
+    Byte,
+    Buffer,
+}
+
+impl std::fmt::Display for BitScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitScope::Byte => write!(f, "byte"),
+            BitScope::Buffer => write!(f, "buffer"),
+        }
     }
-    
-    /// Calculated field: XOR pad byte
-    pub fn xor_pad_byte(&self) -> Option<u8> {
-        u8::from_str_radix(&self.xor_pad, 16).ok()
+}
+
+impl FromStr for BitScope {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "byte" => Ok(BitScope::Byte),
+            "buffer" => Ok(BitScope::Buffer),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid bit scope: {}", s))),
+        }
     }
-    
-    /// Load configuration from command line and optional config file
-    pub fn load() -> Result<Self, ByteProcError> {
-        // Parse command line args first
-        let cli_args = Self::parse(); // Renamed to cli_args to avoid confusion with config variable
-        
-        // Determine if we should load from a file
-        let mut config_from_file = if let Some(path) = &cli_args.config {
-            // Explicitly provided config file
-            Self::from_file(path)?
-        } else if let Ok(env_path) = std::env::var("BYTEPROC_CONFIG") {
-            // Config file from environment variable
-            Self::from_file(&PathBuf::from(env_path))?
-        } else if PathBuf::from("byteproc.json").exists() {
-            // Default config file in current directory
-            Self::from_file(&PathBuf::from("byteproc.json"))?
-        } else {
-            // No config file, use defaults
-            Self::default()
-        };
-        
-        // Override file/default values with CLI values where provided
-        // We check if the CLI arg was actually passed by clap or if it's using its own default.
-        // This requires checking against the default values defined by clap for simple types,
-        // or checking if Option types are Some.
+}
 
-        // Create a default instance of CLI args to compare against
-        let default_cli_args = Self::try_parse_from(&["byteproc"]).unwrap_or_else(|_| Self::default());
+/// Substitution box direction
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SboxMode {
+    #[default]
+    Forward,
+    /// Apply the inverse table, the table must be a permutation
+    Inverse,
+}
+
+impl std::fmt::Display for SboxMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SboxMode::Forward => write!(f, "forward"),
+            SboxMode::Inverse => write!(f, "inverse"),
+        }
+    }
+}
 
+impl FromStr for SboxMode {
+    type Err = ByteProcError;
 
-        if cli_args.max_stream_size_kb != default_cli_args.max_stream_size_kb {
-            config_from_file.max_stream_size_kb = cli_args.max_stream_size_kb;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "forward" => Ok(SboxMode::Forward),
+            "inverse" => Ok(SboxMode::Inverse),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid sbox mode: {}", s))),
         }
-        if cli_args.input_type != default_cli_args.input_type {
-            config_from_file.input_type = cli_args.input_type;
+    }
+}
+
+/// Run-length coding modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RleMode {
+    #[default]
+    Encode,
+    Decode,
+}
+
+impl std::fmt::Display for RleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RleMode::Encode => write!(f, "encode"),
+            RleMode::Decode => write!(f, "decode"),
         }
-        if cli_args.input_zmq_socket.is_some() { // For Option types, just check if Some
-            config_from_file.input_zmq_socket = cli_args.input_zmq_socket;
+    }
+}
+
+impl FromStr for RleMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "encode" => Ok(RleMode::Encode),
+            "decode" => Ok(RleMode::Decode),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid rle mode: {}", s))),
         }
-        if cli_args.input_zmq_bind != default_cli_args.input_zmq_bind {
-             config_from_file.input_zmq_bind = cli_args.input_zmq_bind;
+    }
+}
+
+/// Huffman coding modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HuffmanMode {
+    #[default]
+    Encode,
+    Decode,
+}
+
+impl std::fmt::Display for HuffmanMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HuffmanMode::Encode => write!(f, "encode"),
+            HuffmanMode::Decode => write!(f, "decode"),
         }
-        if cli_args.output_type != default_cli_args.output_type {
-            config_from_file.output_type = cli_args.output_type;
+    }
+}
+
+impl FromStr for HuffmanMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "encode" => Ok(HuffmanMode::Encode),
+            "decode" => Ok(HuffmanMode::Decode),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid huffman mode: {}", s))),
         }
-        if cli_args.output_zmq_socket.is_some() {
-            config_from_file.output_zmq_socket = cli_args.output_zmq_socket;
+    }
+}
+
+/// Repetition code modes
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RepetitionMode {
+    #[default]
+    Encode,
+    /// Majority-vote decode
+    Decode,
+}
+
+impl std::fmt::Display for RepetitionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepetitionMode::Encode => write!(f, "encode"),
+            RepetitionMode::Decode => write!(f, "decode"),
         }
-        if cli_args.output_zmq_bind != default_cli_args.output_zmq_bind {
-            config_from_file.output_zmq_bind = cli_args.output_zmq_bind;
+    }
+}
+
+impl FromStr for RepetitionMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "encode" => Ok(RepetitionMode::Encode),
+            "decode" => Ok(RepetitionMode::Decode),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid repetition mode: {}", s))),
         }
-        if cli_args.zmq_reconnect_interval_ms != default_cli_args.zmq_reconnect_interval_ms {
-            config_from_file.zmq_reconnect_interval_ms = cli_args.zmq_reconnect_interval_ms;
+    }
+}
+
+/// Unit repeated by the repetition code
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RepetitionUnit {
+    /// Each bit is repeated, then the bits are packed
+    #[default]
+    Bit,
+    /// Each byte is repeated
+    Byte,
+}
+
+impl std::fmt::Display for RepetitionUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepetitionUnit::Bit => write!(f, "bit"),
+            RepetitionUnit::Byte => write!(f, "byte"),
         }
-        if cli_args.zmq_max_reconnect_attempts != default_cli_args.zmq_max_reconnect_attempts {
-            config_from_file.zmq_max_reconnect_attempts = cli_args.zmq_max_reconnect_attempts;
+    }
+}
+
+impl FromStr for RepetitionUnit {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bit" => Ok(RepetitionUnit::Bit),
+            "byte" => Ok(RepetitionUnit::Byte),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid repetition unit: {}", s))),
         }
-        if cli_args.zmq_send_timeout_ms != default_cli_args.zmq_send_timeout_ms {
-            config_from_file.zmq_send_timeout_ms = cli_args.zmq_send_timeout_ms;
+    }
+}
+
+/// Symbol mapping direction
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolsMode {
+    /// Packed bytes to one k-bit symbol index per byte
+    #[default]
+    Map,
+    /// Symbol indices back to packed bytes
+    Demap,
+}
+
+impl std::fmt::Display for SymbolsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolsMode::Map => write!(f, "map"),
+            SymbolsMode::Demap => write!(f, "demap"),
         }
-        if cli_args.zmq_receive_timeout_ms != default_cli_args.zmq_receive_timeout_ms {
-            config_from_file.zmq_receive_timeout_ms = cli_args.zmq_receive_timeout_ms;
+    }
+}
+
+impl FromStr for SymbolsMode {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "map" => Ok(SymbolsMode::Map),
+            "demap" => Ok(SymbolsMode::Demap),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid symbols mode: {}", s))),
         }
-        if cli_args.zmq_linger_ms != default_cli_args.zmq_linger_ms {
-            config_from_file.zmq_linger_ms = cli_args.zmq_linger_ms;
+    }
+}
+
+/// What to do when one output fails to take a message
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkErrorPolicy {
+    /// Stop processing with the sink's error
+    #[default]
+    Fail,
+    /// Log the failure and keep delivering to the other outputs
+    Warn,
+}
+
+impl std::fmt::Display for SinkErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkErrorPolicy::Fail => write!(f, "fail"),
+            SinkErrorPolicy::Warn => write!(f, "warn"),
         }
-        if cli_args.log_enabled != default_cli_args.log_enabled {
-            config_from_file.log_enabled = cli_args.log_enabled;
+    }
+}
+
+impl FromStr for SinkErrorPolicy {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(SinkErrorPolicy::Fail),
+            "warn" => Ok(SinkErrorPolicy::Warn),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid sink error policy: {}", s))),
+        }
+    }
+}
+
+/// Use of the inverse pipeline
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InvertPipeline {
+    /// Run the pipeline as configured
+    #[default]
+    Off,
+    /// Print the inverse config as JSON and exit
+    Emit,
+    /// Run the inverse pipeline instead
+    Run,
+}
+
+impl std::fmt::Display for InvertPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvertPipeline::Off => write!(f, "off"),
+            InvertPipeline::Emit => write!(f, "emit"),
+            InvertPipeline::Run => write!(f, "run"),
+        }
+    }
+}
+
+impl FromStr for InvertPipeline {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(InvertPipeline::Off),
+            "emit" => Ok(InvertPipeline::Emit),
+            "run" => Ok(InvertPipeline::Run),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid invert pipeline mode: {}", s))),
+        }
+    }
+}
+
+/// What to do with a message that fails to decode or process
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Stop with the error
+    #[default]
+    Abort,
+    /// Log the error and go on with the next message
+    Skip,
+    /// Send the input and the error to the dead-letter output, then go on
+    DeadLetter,
+}
+
+impl std::fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorPolicy::Abort => write!(f, "abort"),
+            ErrorPolicy::Skip => write!(f, "skip"),
+            ErrorPolicy::DeadLetter => write!(f, "dead_letter"),
+        }
+    }
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(ErrorPolicy::Abort),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "dead_letter" => Ok(ErrorPolicy::DeadLetter),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid error policy: {}", s))),
+        }
+    }
+}
+
+/// What a pipeline stage does when its module fails
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StageErrorPolicy {
+    /// Fail the message
+    #[default]
+    Fail,
+    /// Forward the stage's unmodified input
+    Passthrough,
+    /// Drop the message without output
+    Drop,
+}
+
+impl std::fmt::Display for StageErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StageErrorPolicy::Fail => write!(f, "fail"),
+            StageErrorPolicy::Passthrough => write!(f, "passthrough"),
+            StageErrorPolicy::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+impl FromStr for StageErrorPolicy {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(StageErrorPolicy::Fail),
+            "passthrough" => Ok(StageErrorPolicy::Passthrough),
+            "drop" => Ok(StageErrorPolicy::Drop),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid stage error policy: {}", s))),
+        }
+    }
+}
+
+/// How a failed run reports its error on stderr
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// `Error: <message>`
+    #[default]
+    Text,
+    /// One JSON object, see `ErrorReport`
+    Json,
+}
+
+impl std::fmt::Display for ErrorFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorFormat::Text => write!(f, "text"),
+            ErrorFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid error format: {}", s))),
+        }
+    }
+}
+
+/// ZMQ socket type for publishing log records
+#[derive(Parser, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogZmqType {
+    /// PUSH: every record reaches one collector
+    #[default]
+    Push,
+    /// PUB: every connected collector gets every record
+    Pub,
+}
+
+impl std::fmt::Display for LogZmqType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogZmqType::Push => write!(f, "push"),
+            LogZmqType::Pub => write!(f, "pub"),
+        }
+    }
+}
+
+impl FromStr for LogZmqType {
+    type Err = ByteProcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "push" => Ok(LogZmqType::Push),
+            "pub" => Ok(LogZmqType::Pub),
+            _ => Err(ByteProcError::InvalidConfiguration(format!("Invalid log_zmq_type: {}", s))),
+        }
+    }
+}
+
+// Module name constants
+pub const MODULE_PASSTHROUGH: &str = "passthrough";
+pub const MODULE_XOR: &str = "xor";
+pub const MODULE_BASE64: &str = "base64";
+pub const MODULE_LINECODE: &str = "linecode";
+pub const MODULE_BITS: &str = "bits";
+pub const MODULE_FRAMER: &str = "framer";
+pub const MODULE_DEFRAMER: &str = "deframer";
+pub const MODULE_CHANNEL: &str = "channel";
+pub const MODULE_SLICE: &str = "slice";
+pub const MODULE_PAD: &str = "pad";
+pub const MODULE_PREPEND: &str = "prepend";
+pub const MODULE_APPEND: &str = "append";
+pub const MODULE_REVERSE: &str = "reverse";
+pub const MODULE_SWAP: &str = "swap";
+pub const MODULE_BITREVERSE: &str = "bitreverse";
+pub const MODULE_BITOPS: &str = "bitops";
+pub const MODULE_SBOX: &str = "sbox";
+pub const MODULE_RLE: &str = "rle";
+pub const MODULE_HUFFMAN: &str = "huffman";
+pub const MODULE_REPETITION: &str = "repetition";
+pub const MODULE_SYMBOLS: &str = "symbols";
+pub const MODULE_SCRIPT: &str = "script";
+pub const MODULE_WASM: &str = "wasm";
+
+/// Built-in modules in the order they run when no `pipeline` is configured
+pub const BUILTIN_MODULES: &[&str] = &[
+    MODULE_PASSTHROUGH,
+    MODULE_XOR,
+    MODULE_BASE64,
+    MODULE_LINECODE,
+    MODULE_BITS,
+    MODULE_FRAMER,
+    MODULE_DEFRAMER,
+    MODULE_CHANNEL,
+    MODULE_SLICE,
+    MODULE_REVERSE,
+    MODULE_SWAP,
+    MODULE_BITREVERSE,
+    MODULE_PAD,
+    MODULE_PREPEND,
+    MODULE_APPEND,
+    MODULE_BITOPS,
+    MODULE_SBOX,
+    MODULE_RLE,
+    MODULE_HUFFMAN,
+    MODULE_REPETITION,
+    MODULE_SYMBOLS,
+    MODULE_SCRIPT,
+    MODULE_WASM,
+];
+
+// -------------- Error type --------------
+
+#[derive(Debug)]
+pub enum ByteProcError {
+    Io(String),
+    InvalidConfiguration(String),
+    HexDecode(String),
+    MaxSizeExceeded(usize, usize),
+    Zmq(String),
+    Module(String),
+    /// Module input rejected at a known byte offset
+    InvalidInput { offset: usize, message: String },
+    /// A pipeline stage failed; `source` is the module's own error
+    Stage { stage: String, source: Box<ByteProcError> },
+}
+
+impl fmt::Display for ByteProcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteProcError::Io(e) => write!(f, "I/O error: {}", e),
+            ByteProcError::InvalidConfiguration(e) => write!(f, "Invalid configuration: {}", e),
+            ByteProcError::HexDecode(e) => write!(f, "Hex decode error: {}", e),
+            ByteProcError::MaxSizeExceeded(max, got) => {
+                write!(f, "Stream too large: max {} bytes, got {}", max, got)
+            }
+            ByteProcError::Zmq(e) => write!(f, "ZeroMQ error: {}", e),
+            ByteProcError::Module(e) => write!(f, "Module processing error: {}", e),
+            ByteProcError::InvalidInput { offset, message } => {
+                write!(f, "Invalid input at byte {}: {}", offset, message)
+            }
+            ByteProcError::Stage { stage, source } => write!(f, "Stage {} failed: {}", stage, source),
+        }
+    }
+}
+
+impl ByteProcError {
+    /// Short stable name of the error variant, for machine-readable output.
+    /// Stage errors report the kind of the module's error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ByteProcError::Io(_) => "io",
+            ByteProcError::InvalidConfiguration(_) => "invalid_configuration",
+            ByteProcError::HexDecode(_) => "hex_decode",
+            ByteProcError::MaxSizeExceeded(_, _) => "max_size_exceeded",
+            ByteProcError::Zmq(_) => "zmq",
+            ByteProcError::Module(_) => "module",
+            ByteProcError::InvalidInput { .. } => "invalid_input",
+            ByteProcError::Stage { source, .. } => source.kind(),
+        }
+    }
+
+    /// Process exit code, following sysexits.h:
+    ///
+    /// | kind | code |
+    /// |------|------|
+    /// | `hex_decode`, `max_size_exceeded`, `invalid_input` | 65 (`EX_DATAERR`) |
+    /// | `zmq` | 69 (`EX_UNAVAILABLE`) |
+    /// | `module` | 70 (`EX_SOFTWARE`) |
+    /// | `io` | 74 (`EX_IOERR`) |
+    /// | `invalid_configuration` | 78 (`EX_CONFIG`) |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ByteProcError::HexDecode(_)
+            | ByteProcError::MaxSizeExceeded(_, _)
+            | ByteProcError::InvalidInput { .. } => 65,
+            ByteProcError::Zmq(_) => 69,
+            ByteProcError::Module(_) => 70,
+            ByteProcError::Io(_) => 74,
+            ByteProcError::InvalidConfiguration(_) => 78,
+            ByteProcError::Stage { source, .. } => source.exit_code(),
+        }
+    }
+
+    /// Name of the pipeline stage that failed, if any
+    pub fn stage(&self) -> Option<&str> {
+        match self {
+            ByteProcError::Stage { stage, .. } => Some(stage),
+            _ => None,
+        }
+    }
+
+    /// Byte offset in the failing module's input, if known
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ByteProcError::InvalidInput { offset, .. } => Some(*offset),
+            ByteProcError::Stage { source, .. } => source.offset(),
+            _ => None,
+        }
+    }
+}
+
+impl Error for ByteProcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ByteProcError::Stage { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The message being processed when an error occurred
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputSummary {
+    pub source: String,
+    pub sequence: u64,
+    /// Length of the hex input in characters
+    pub hex_chars: usize,
+    /// Up to the first 32 hex characters
+    pub head: String,
+}
+
+impl InputSummary {
+    pub fn new(raw_hex: &str, ctx: &ProcessContext) -> Self {
+        InputSummary {
+            source: ctx.source.clone(),
+            sequence: ctx.sequence,
+            hex_chars: raw_hex.len(),
+            head: raw_hex.chars().take(32).collect(),
+        }
+    }
+}
+
+/// Error that stopped a message, with the message it stopped. Displays as
+/// the error itself.
+#[derive(Debug)]
+pub struct MessageError {
+    pub input: InputSummary,
+    pub error: ByteProcError,
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for MessageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Machine-readable description of a failed run, printed on stderr with
+/// `--error-format json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// `ByteProcError::kind`, or `other` for errors from outside byteproc
+    pub kind: String,
+    /// Pipeline stage that failed, if any
+    pub module: Option<String>,
+    /// Byte offset in the module's input, if known
+    pub offset: Option<usize>,
+    pub message: String,
+    pub exit_code: i32,
+    pub instance_id: String,
+    /// Message being processed, if the error happened while handling one
+    pub input: Option<InputSummary>,
+}
+
+impl ErrorReport {
+    pub fn new(err: &(dyn Error + 'static), input: Option<InputSummary>) -> Self {
+        let bp = err.downcast_ref::<ByteProcError>();
+        ErrorReport {
+            kind: bp.map_or("other", ByteProcError::kind).to_string(),
+            module: bp.and_then(ByteProcError::stage).map(str::to_string),
+            offset: bp.and_then(ByteProcError::offset),
+            message: err.to_string(),
+            exit_code: bp.map_or(1, ByteProcError::exit_code),
+            instance_id: make_instance_id().to_string(),
+            input,
+        }
+    }
+
+    /// Report for an error returned by `main_internal`; a `MessageError`
+    /// supplies the input
+    pub fn from_error(err: &(dyn Error + 'static)) -> Self {
+        match err.downcast_ref::<MessageError>() {
+            Some(failed) => ErrorReport::new(&failed.error, Some(failed.input.clone())),
+            None => ErrorReport::new(err, None),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Plain strings and integers only, serialization cannot fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+// -------------- ByteProcessor trait --------------
+
+pub trait ByteProcessor {
+    fn name(&self) -> &'static str;
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError>;
+
+    /// Like `process`, with access to the message context. Modules that
+    /// record metadata override this and implement `process` on top of it.
+    fn process_with_context(&self, input: &[u8], ctx: &mut ProcessContext) -> Result<Vec<u8>, ByteProcError> {
+        let _ = ctx;
+        self.process(input)
+    }
+
+    /// Incremental version of this module, for modules that can work on
+    /// chunks without seeing the whole message. Others are buffered.
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        None
+    }
+
+    /// Inverse support, for modules that implement `Invertible`
+    fn as_invertible(&self) -> Option<&dyn Invertible> {
+        None
+    }
+}
+
+/// Modules whose effect another module can undo, so a decode pipeline can
+/// be derived from an encode pipeline
+pub trait Invertible {
+    /// Write the settings of the module that reverses this one into `cfg`
+    /// and return its module name. This is the single description of the
+    /// inverse: `invert`, `ModuleRegistry::inverse` and `Config::inverted`
+    /// are all built from it.
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError>;
+
+    /// Module whose `process` reverses this module's `process`
+    fn invert(&self) -> Result<Box<dyn ByteProcessor>, ByteProcError> {
+        let mut cfg = Config::default();
+        let name = self.invert_config(&mut cfg)?;
+        create_module(name, &cfg)
+    }
+}
+
+/// Per-message context carried through the pipeline next to the bytes.
+/// Over ZeroMQ it travels as a JSON second frame.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessContext {
+    /// Message sequence number, assigned by the first instance: its start
+    /// time in µs plus the number of messages it received before this one
+    #[serde(default)]
+    pub sequence: u64,
+    /// Where this instance received the message from
+    #[serde(default)]
+    pub source: String,
+    /// When the first instance received the message (µs since the Unix epoch)
+    #[serde(default)]
+    pub created_us: u64,
+    /// When this instance received the message (µs since the Unix epoch)
+    #[serde(default)]
+    pub received_us: u64,
+    /// Key/value metadata; modules write `<module>.<key>` entries
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+impl ProcessContext {
+    /// Context for a message entering the first instance
+    pub fn new(sequence: u64, source: &str) -> Self {
+        let now = unix_time_us();
+        ProcessContext { sequence, source: source.to_string(), created_us: now, received_us: now, metadata: BTreeMap::new() }
+    }
+
+    /// Context for a message that arrived with upstream context; keeps its
+    /// sequence, creation time and metadata
+    pub fn received(upstream: ProcessContext, source: &str) -> Self {
+        ProcessContext { source: source.to_string(), received_us: unix_time_us(), ..upstream }
+    }
+
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        self.metadata.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self, ByteProcError> {
+        serde_json::from_slice(json)
+            .map_err(|e| ByteProcError::InvalidConfiguration(format!("Invalid message context: {}", e)))
+    }
+}
+
+/// Chunked counterpart of `ByteProcessor`. `update` returns the output
+/// that is ready so far and `finish` flushes whatever was held back.
+pub trait StreamProcessor {
+    fn name(&self) -> &'static str;
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError>;
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError>;
+}
+
+// -------------- Modules --------------
+
+/// Passthrough
+#[derive(Debug)]
+pub struct Passthrough;
+impl Invertible for Passthrough {
+    fn invert_config(&self, _cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        Ok(MODULE_PASSTHROUGH)
+    }
+}
+impl ByteProcessor for Passthrough {
+    fn name(&self) -> &'static str { MODULE_PASSTHROUGH }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        Ok(input.to_vec())
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(BytewiseStream { module: self }))
+    }
+}
+
+/// XOR
+#[derive(Debug)]
+pub struct XorModule {
+    key: XorKey,
+}
+#[derive(Debug)]
+struct XorKey { key: Vec<u8> }
+impl Drop for XorKey { fn drop(&mut self) { self.key.zeroize(); } }
+impl XorModule {
+    pub fn new(hex_key: &str, pad_byte: Option<u8>) -> Result<Self, ByteProcError> {
+        let raw = Vec::from_hex(hex_key)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        if raw.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration("xor_key cannot be empty".into()));
+        }
+        // pad or cycle?
+        let _pad = pad_byte.unwrap_or(0);
+        // Note: we'll cycle if pad_byte is None; no further action here
+        Ok(XorModule {
+            key: XorKey { key: raw },
+        })
+    }
+}
+impl Invertible for XorModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        // XOR is its own inverse
+        cfg.xor_key = Some(hex::encode(&self.key.key));
+        Ok(MODULE_XOR)
+    }
+}
+impl ByteProcessor for XorModule {
+    fn name(&self) -> &'static str { MODULE_XOR }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut out = Vec::with_capacity(input.len());
+        let key = &self.key.key;
+        for (i, &b) in input.iter().enumerate() {
+            let k = key[i % key.len()];
+            out.push(b ^ k);
+        }
+        Ok(out)
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(XorStream { key: &self.key.key, position: 0 }))
+    }
+}
+
+/// Base64
+#[derive(Debug)]
+pub struct Base64Module {
+    encode: bool,
+    padding: bool,
+}
+impl Base64Module {
+    pub fn new(encode: bool, padding: bool) -> Self {
+        Base64Module { encode, padding }
+    }
+
+    fn engine(&self) -> &'static base64::engine::GeneralPurpose {
+        if self.padding {
+            &base64::engine::general_purpose::STANDARD
+        } else {
+            &base64::engine::general_purpose::STANDARD_NO_PAD
+        }
+    }
+}
+impl Invertible for Base64Module {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.base64_mode = if self.encode { Base64Mode::Decode } else { Base64Mode::Encode };
+        cfg.base64_padding = self.padding;
+        Ok(MODULE_BASE64)
+    }
+}
+impl ByteProcessor for Base64Module {
+    fn name(&self) -> &'static str { MODULE_BASE64 }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.encode {
+            Ok(self.engine().encode(input).into_bytes())
+        } else {
+            self.engine().decode(input).map_err(|e| {
+                let offset = match e {
+                    base64::DecodeError::InvalidByte(offset, _)
+                    | base64::DecodeError::InvalidLastSymbol(offset, _) => offset,
+                    base64::DecodeError::InvalidLength | base64::DecodeError::InvalidPadding => input.len(),
+                };
+                ByteProcError::InvalidInput { offset, message: format!("base64 decode: {}", e) }
+            })
+        }
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(Base64Stream { module: self, carry: Vec::new() }))
+    }
+}
+
+/// Line coding (Manchester, differential Manchester, NRZI, differential)
+///
+/// Works on the bit stream, MSB first. The line-coded side is either packed
+/// bytes or, when `unpacked` is set, one bit (0 or 1) per byte as used by
+/// GNU Radio. A trailing partial byte on packed output is zero padded.
+#[derive(Debug)]
+pub struct LineCodeModule {
+    scheme: LineCodeScheme,
+    encode: bool,
+    unpacked: bool,
+}
+impl LineCodeModule {
+    pub fn new(scheme: LineCodeScheme, encode: bool, unpacked: bool) -> Self {
+        LineCodeModule { scheme, encode, unpacked }
+    }
+
+    fn encode_bits(&self, bits: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(bits.len() * 2);
+        let mut level = 0u8;
+        for &b in bits {
+            match self.scheme {
+                LineCodeScheme::ManchesterIeee => out.extend_from_slice(&[b ^ 1, b]),
+                LineCodeScheme::ManchesterThomas => out.extend_from_slice(&[b, b ^ 1]),
+                LineCodeScheme::DiffManchester => {
+                    if b == 0 {
+                        level ^= 1;
+                    }
+                    out.push(level);
+                    level ^= 1;
+                    out.push(level);
+                }
+                LineCodeScheme::Nrzi => {
+                    if b == 0 {
+                        level ^= 1;
+                    }
+                    out.push(level);
+                }
+                LineCodeScheme::Differential => {
+                    level ^= b;
+                    out.push(level);
+                }
+            }
+        }
+        out
+    }
+
+    fn decode_bits(&self, chips: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let two_chip = matches!(
+            self.scheme,
+            LineCodeScheme::ManchesterIeee | LineCodeScheme::ManchesterThomas | LineCodeScheme::DiffManchester
+        );
+        // Only unpacked input can hold an odd number of chips
+        if two_chip && chips.len() % 2 == 1 {
+            return Err(ByteProcError::InvalidInput {
+                offset: chips.len() - 1,
+                message: format!("{} decode needs an even number of chips, got {}", self.scheme, chips.len()),
+            });
+        }
+
+        let mut out = Vec::with_capacity(chips.len());
+        let mut prev = 0u8;
+        if two_chip {
+            for (i, pair) in chips.chunks_exact(2).enumerate() {
+                let (c0, c1) = (pair[0], pair[1]);
+                if c0 == c1 {
+                    return Err(ByteProcError::InvalidInput {
+                        offset: if self.unpacked { i * 2 } else { i * 2 / 8 },
+                        message: format!("{} decode: missing mid-bit transition at bit {}", self.scheme, i),
+                    });
+                }
+                out.push(match self.scheme {
+                    LineCodeScheme::ManchesterIeee => c1,
+                    LineCodeScheme::ManchesterThomas => c0,
+                    _ => (c0 == prev) as u8,
+                });
+                prev = c1;
+            }
+        } else {
+            for &c in chips {
+                out.push(match self.scheme {
+                    LineCodeScheme::Nrzi => (c == prev) as u8,
+                    _ => c ^ prev,
+                });
+                prev = c;
+            }
+        }
+        Ok(out)
+    }
+}
+impl Invertible for LineCodeModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.linecode_scheme = self.scheme;
+        cfg.linecode_mode = if self.encode { LineCodeMode::Decode } else { LineCodeMode::Encode };
+        cfg.linecode_unpacked = self.unpacked;
+        Ok(MODULE_LINECODE)
+    }
+}
+impl ByteProcessor for LineCodeModule {
+    fn name(&self) -> &'static str { MODULE_LINECODE }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.encode {
+            let chips = self.encode_bits(&bytes_to_bits(input, BitOrder::Msb));
+            if self.unpacked {
+                Ok(chips)
+            } else {
+                Ok(bits_to_bytes(&chips, BitOrder::Msb))
+            }
+        } else {
+            let chips = if self.unpacked {
+                check_unpacked_bits(input)?;
+                input.to_vec()
+            } else {
+                bytes_to_bits(input, BitOrder::Msb)
+            };
+            let bits = self.decode_bits(&chips)?;
+            if bits.len() % 8 != 0 {
+                warn!(
+                    "[{}] {} decode produced {} bits, zero padding the last byte",
+                    make_instance_id(), self.scheme, bits.len()
+                );
+            }
+            Ok(bits_to_bytes(&bits, BitOrder::Msb))
+        }
+    }
+}
+
+/// Bit unpack/pack (GNU Radio `packed_to_unpacked` / `unpacked_to_packed`)
+///
+/// Unpack splits the bit stream into `bits_per_byte`-bit chunks and writes
+/// each chunk into the low bits of an output byte. Pack does the reverse.
+/// `order` selects whether bits are read from (and placed into) each byte
+/// MSB or LSB first.
+#[derive(Debug)]
+pub struct BitsModule {
+    unpack: bool,
+    bits_per_byte: usize,
+    order: BitOrder,
+}
+impl BitsModule {
+    pub fn new(unpack: bool, bits_per_byte: u8, order: BitOrder) -> Result<Self, ByteProcError> {
+        if !(1..=8).contains(&bits_per_byte) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "bits_per_byte must be between 1 and 8, got {}",
+                bits_per_byte
+            )));
+        }
+        Ok(BitsModule { unpack, bits_per_byte: bits_per_byte as usize, order })
+    }
+}
+impl Invertible for BitsModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.bits_mode = if self.unpack { BitsMode::Pack } else { BitsMode::Unpack };
+        cfg.bits_per_byte = self.bits_per_byte as u8;
+        cfg.bits_order = self.order;
+        Ok(MODULE_BITS)
+    }
+}
+impl ByteProcessor for BitsModule {
+    fn name(&self) -> &'static str { MODULE_BITS }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let k = self.bits_per_byte;
+        if self.unpack {
+            let bits = bytes_to_bits(input, self.order);
+            if !bits.len().is_multiple_of(k) {
+                warn!(
+                    "[{}] {} bits do not split into {}-bit chunks, zero padding the last chunk",
+                    make_instance_id(), bits.len(), k
+                );
+            }
+            Ok(bits
+                .chunks(k)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (i, &b)| acc | (b << bit_shift(i, k, self.order)))
+                })
+                .collect())
+        } else {
+            let mut bits = Vec::with_capacity(input.len() * k);
+            for (pos, &value) in input.iter().enumerate() {
+                if k < 8 && value >> k != 0 {
+                    return Err(ByteProcError::InvalidInput {
+                        offset: pos,
+                        message: format!("value 0x{:02x} does not fit in {} bits", value, k),
+                    });
+                }
+                for i in 0..k {
+                    bits.push((value >> bit_shift(i, k, self.order)) & 1);
+                }
+            }
+            if !bits.len().is_multiple_of(8) {
+                warn!(
+                    "[{}] packing {} bits, zero padding the last byte",
+                    make_instance_id(), bits.len()
+                );
+            }
+            Ok(bits_to_bytes(&bits, self.order))
+        }
+    }
+}
+
+/// Over-the-air packet layout shared by the framer and deframer:
+/// `preamble | sync word | length | payload | crc`
+///
+/// The length field counts payload bytes only. The CRC covers the length
+/// field and the payload and is written with the same endianness as the
+/// length field.
+#[derive(Debug, Clone)]
+pub struct FrameFormat {
+    preamble: Vec<u8>,
+    sync_word: Vec<u8>,
+    length_bytes: usize,
+    length_endian: Endianness,
+    crc: FrameCrc,
+}
+impl FrameFormat {
+    pub fn new(
+        preamble_hex: &str,
+        sync_word_hex: &str,
+        length_bytes: u8,
+        length_endian: Endianness,
+        crc: FrameCrc,
+    ) -> Result<Self, ByteProcError> {
+        let preamble = Vec::from_hex(preamble_hex)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        let sync_word = Vec::from_hex(sync_word_hex)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        if sync_word.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration("framer_sync_word cannot be empty".into()));
+        }
+        if ![1, 2, 4].contains(&length_bytes) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "framer_length_bytes must be 1, 2 or 4, got {}",
+                length_bytes
+            )));
+        }
+        Ok(FrameFormat {
+            preamble,
+            sync_word,
+            length_bytes: length_bytes as usize,
+            length_endian,
+            crc,
+        })
+    }
+
+    /// Store this format in the framer_* fields of `cfg`
+    fn write_config(&self, cfg: &mut Config) {
+        cfg.framer_preamble = hex::encode(&self.preamble);
+        cfg.framer_sync_word = hex::encode(&self.sync_word);
+        cfg.framer_length_bytes = self.length_bytes as u8;
+        cfg.framer_length_endian = self.length_endian;
+        cfg.framer_crc = self.crc;
+    }
+
+    fn crc_bytes(&self) -> usize {
+        match self.crc {
+            FrameCrc::None => 0,
+            FrameCrc::Crc16 => 2,
+            FrameCrc::Crc32 => 4,
+        }
+    }
+
+    fn checksum(&self, data: &[u8]) -> Vec<u8> {
+        match self.crc {
+            FrameCrc::None => Vec::new(),
+            FrameCrc::Crc16 => encode_uint(crc16_ccitt(data) as u64, 2, self.length_endian),
+            FrameCrc::Crc32 => encode_uint(crc32(data) as u64, 4, self.length_endian),
+        }
+    }
+}
+
+/// Framer: wraps the input in a single packet
+#[derive(Debug)]
+pub struct FramerModule {
+    format: FrameFormat,
+}
+impl FramerModule {
+    pub fn new(format: FrameFormat) -> Self {
+        FramerModule { format }
+    }
+}
+impl Invertible for FramerModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        self.format.write_config(cfg);
+        cfg.deframer_max_bit_errors = 0;
+        cfg.deframer_search = FrameSearch::Byte;
+        Ok(MODULE_DEFRAMER)
+    }
+}
+impl ByteProcessor for FramerModule {
+    fn name(&self) -> &'static str { MODULE_FRAMER }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let f = &self.format;
+        let max_len = (1u64 << (f.length_bytes * 8)) - 1;
+        if input.len() as u64 > max_len {
+            return Err(ByteProcError::Module(format!(
+                "payload of {} bytes does not fit a {}-byte length field",
+                input.len(),
+                f.length_bytes
+            )));
+        }
+        let mut body = encode_uint(input.len() as u64, f.length_bytes, f.length_endian);
+        body.extend_from_slice(input);
+        let crc = f.checksum(&body);
+
+        let mut out = Vec::with_capacity(f.preamble.len() + f.sync_word.len() + body.len() + crc.len());
+        out.extend_from_slice(&f.preamble);
+        out.extend_from_slice(&f.sync_word);
+        out.extend_from_slice(&body);
+        out.extend_from_slice(&crc);
+        Ok(out)
+    }
+}
+
+/// Deframer: searches the input for the sync word and extracts the payload
+/// of every packet found, concatenated in stream order
+///
+/// The sync word matches when it differs in at most `max_bit_errors` bits.
+/// Packets that are truncated or fail the CRC are logged and skipped.
+#[derive(Debug)]
+pub struct DeframerModule {
+    format: FrameFormat,
+    max_bit_errors: u32,
+    search: FrameSearch,
+}
+impl DeframerModule {
+    pub fn new(format: FrameFormat, max_bit_errors: u32, search: FrameSearch) -> Result<Self, ByteProcError> {
+        if max_bit_errors as usize >= format.sync_word.len() * 8 {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "deframer_max_bit_errors ({}) must be smaller than the sync word length in bits ({})",
+                max_bit_errors,
+                format.sync_word.len() * 8
+            )));
+        }
+        Ok(DeframerModule { format, max_bit_errors, search })
+    }
+}
+impl Invertible for DeframerModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        // Frames the concatenated payloads as one packet
+        self.format.write_config(cfg);
+        Ok(MODULE_FRAMER)
+    }
+}
+impl ByteProcessor for DeframerModule {
+    fn name(&self) -> &'static str { MODULE_DEFRAMER }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.process_with_context(input, &mut ProcessContext::default())
+    }
+    fn process_with_context(&self, input: &[u8], ctx: &mut ProcessContext) -> Result<Vec<u8>, ByteProcError> {
+        let f = &self.format;
+        let instance_id = make_instance_id();
+        let bits = bytes_to_bits(input, BitOrder::Msb);
+        let sync = bytes_to_bits(&f.sync_word, BitOrder::Msb);
+        let step = match self.search {
+            FrameSearch::Byte => 8,
+            FrameSearch::Bit => 1,
+        };
+
+        let mut out = Vec::new();
+        let mut found = 0usize;
+        let mut crc_failures = 0usize;
+        let mut pos = 0usize;
+        while pos + sync.len() <= bits.len() {
+            let distance = bits[pos..pos + sync.len()]
+                .iter()
+                .zip(&sync)
+                .filter(|(a, b)| a != b)
+                .count();
+            if distance > self.max_bit_errors as usize {
+                pos += step;
+                continue;
+            }
+
+            let header_start = pos + sync.len();
+            let Some(header) = bits_at(&bits, header_start, f.length_bytes) else {
+                warn!("[{}] Truncated frame header at bit {}", instance_id, pos);
+                break;
+            };
+            let length = decode_uint(&header, f.length_endian) as usize;
+            let body_bytes = f.length_bytes + length;
+            let Some(body) = bits_at(&bits, header_start, body_bytes + f.crc_bytes()) else {
+                warn!(
+                    "[{}] Truncated frame at bit {} (length field {} bytes)",
+                    instance_id, pos, length
+                );
+                pos += step;
+                continue;
+            };
+            let (body, crc) = body.split_at(body_bytes);
+            if f.checksum(body) != crc {
+                warn!("[{}] CRC mismatch for frame at bit {}, skipping", instance_id, pos);
+                crc_failures += 1;
+                pos += step;
+                continue;
+            }
+
+            info!(
+                "[{}] Frame at bit {} ({} sync bit errors, {} payload bytes)",
+                instance_id, pos, distance, length
+            );
+            out.extend_from_slice(&body[f.length_bytes..]);
+            found += 1;
+            pos = header_start + (body_bytes + f.crc_bytes()) * 8;
+        }
+
+        info!("[{}] Deframer extracted {} frame(s)", instance_id, found);
+        ctx.set("deframer.frames", found);
+        ctx.set("deframer.crc_failures", crc_failures);
+        Ok(out)
+    }
+}
+
+/// Channel simulation settings
+#[derive(Debug, Clone)]
+pub struct ChannelParams {
+    pub model: ChannelModel,
+    pub seed: u64,
+    /// Bit error rate (the good state BER for Gilbert-Elliott)
+    pub ber: f64,
+    /// Gilbert-Elliott per-bit probability of entering the bad state
+    pub p_good_to_bad: f64,
+    /// Gilbert-Elliott per-bit probability of leaving the bad state
+    pub p_bad_to_good: f64,
+    /// Gilbert-Elliott bit error rate in the bad state
+    pub ber_bad: f64,
+    /// Per-byte probability of replacing a byte with `erasure_byte`
+    pub erasure_rate: f64,
+    pub erasure_byte: u8,
+    /// Number of bytes dropped from the end of the message
+    pub truncate_bytes: usize,
+}
+
+/// Channel simulation: injects bit errors, burst errors, erasures and
+/// truncation driven by a seeded RNG, so the same seed and input always
+/// produce the same output
+#[derive(Debug)]
+pub struct ChannelModule {
+    params: ChannelParams,
+    // RNG and Gilbert-Elliott state carry over between messages, so each
+    // message sees new errors while a run stays reproducible for a seed
+    state: RefCell<(SimRng, bool)>,
+}
+impl ChannelModule {
+    pub fn new(params: ChannelParams) -> Result<Self, ByteProcError> {
+        for (name, p) in [
+            ("channel_ber", params.ber),
+            ("channel_ge_p_good_to_bad", params.p_good_to_bad),
+            ("channel_ge_p_bad_to_good", params.p_bad_to_good),
+            ("channel_ge_ber_bad", params.ber_bad),
+            ("channel_erasure_rate", params.erasure_rate),
+        ] {
+            if !(0.0..=1.0).contains(&p) {
+                return Err(ByteProcError::InvalidConfiguration(format!(
+                    "{} must be between 0 and 1, got {}",
+                    name, p
+                )));
+            }
+        }
+        let state = RefCell::new((SimRng::new(params.seed), false));
+        Ok(ChannelModule { params, state })
+    }
+}
+impl ByteProcessor for ChannelModule {
+    fn name(&self) -> &'static str { MODULE_CHANNEL }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.process_with_context(input, &mut ProcessContext::default())
+    }
+    fn process_with_context(&self, input: &[u8], ctx: &mut ProcessContext) -> Result<Vec<u8>, ByteProcError> {
+        let p = &self.params;
+        let mut state = self.state.borrow_mut();
+        let (rng, bad_state) = &mut *state;
+        let mut out = input.to_vec();
+
+        // Bit errors
+        let mut bit_errors = 0usize;
+        let mut burst_errors = 0usize;
+        for byte in out.iter_mut() {
+            for bit in 0..8 {
+                let ber = match p.model {
+                    ChannelModel::Bsc => p.ber,
+                    ChannelModel::GilbertElliott => {
+                        let flip_state = if *bad_state { p.p_bad_to_good } else { p.p_good_to_bad };
+                        if rng.next_f64() < flip_state {
+                            *bad_state = !*bad_state;
+                        }
+                        if *bad_state { p.ber_bad } else { p.ber }
+                    }
+                };
+                if rng.next_f64() < ber {
+                    *byte ^= 0x80 >> bit;
+                    bit_errors += 1;
+                    if *bad_state {
+                        burst_errors += 1;
+                    }
+                }
+            }
+        }
+
+        // Erasures
+        let mut erasures = 0usize;
+        if p.erasure_rate > 0.0 {
+            for byte in out.iter_mut() {
+                if rng.next_f64() < p.erasure_rate {
+                    *byte = p.erasure_byte;
+                    erasures += 1;
+                }
+            }
+        }
+
+        // Truncation
+        let truncated = p.truncate_bytes.min(out.len());
+        out.truncate(out.len() - truncated);
+
+        info!(
+            "[{}] Channel ({}, seed {}) injected {} bit errors ({} in bad state), {} erasures, truncated {} bytes",
+            make_instance_id(), p.model, p.seed, bit_errors, burst_errors, erasures, truncated
+        );
+        ctx.set("channel.bit_errors", bit_errors);
+        ctx.set("channel.erasures", erasures);
+        ctx.set("channel.truncated_bytes", truncated);
+        Ok(out)
+    }
+}
+
+/// Slice: keep `length` bytes starting at `offset` (to the end if no length)
+#[derive(Debug)]
+pub struct SliceModule {
+    offset: usize,
+    length: Option<usize>,
+}
+impl SliceModule {
+    pub fn new(offset: usize, length: Option<usize>) -> Self {
+        SliceModule { offset, length }
+    }
+}
+impl ByteProcessor for SliceModule {
+    fn name(&self) -> &'static str { MODULE_SLICE }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let end = match self.length {
+            Some(len) => self.offset.checked_add(len),
+            None => Some(input.len().max(self.offset)),
+        };
+        match end {
+            Some(end) if end <= input.len() => Ok(input[self.offset..end].to_vec()),
+            _ => Err(ByteProcError::InvalidInput {
+                offset: input.len(),
+                message: format!(
+                    "slice offset {} length {:?} is out of range for {} bytes",
+                    self.offset,
+                    self.length,
+                    input.len()
+                ),
+            }),
+        }
+    }
+}
+
+/// Block padding and unpadding
+#[derive(Debug)]
+pub struct PadModule {
+    pad: bool,
+    scheme: PadScheme,
+    block_size: usize,
+}
+impl PadModule {
+    pub fn new(pad: bool, scheme: PadScheme, block_size: usize) -> Result<Self, ByteProcError> {
+        if block_size == 0 || (scheme == PadScheme::Pkcs7 && block_size > 255) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "pad_block_size {} is invalid for {} (1-255 for pkcs7)",
+                block_size, scheme
+            )));
+        }
+        Ok(PadModule { pad, scheme, block_size })
+    }
+
+    fn unpad(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let bad = |offset: usize, why: &str| ByteProcError::InvalidInput {
+            offset,
+            message: format!("invalid {} padding: {}", self.scheme, why),
+        };
+        match self.scheme {
+            PadScheme::Pkcs7 => {
+                if input.is_empty() || !input.len().is_multiple_of(self.block_size) {
+                    return Err(bad(input.len(), "length is not a multiple of the block size"));
+                }
+                let n = input[input.len() - 1] as usize;
+                if n == 0 || n > self.block_size || input[input.len() - n..].iter().any(|&b| b as usize != n) {
+                    return Err(bad(input.len() - 1, "malformed padding bytes"));
+                }
+                Ok(input[..input.len() - n].to_vec())
+            }
+            PadScheme::Zero => {
+                let end = input.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+                Ok(input[..end].to_vec())
+            }
+            PadScheme::Iso7816 => {
+                match input.iter().rposition(|&b| b != 0) {
+                    Some(i) if input[i] == 0x80 => Ok(input[..i].to_vec()),
+                    last => Err(bad(last.unwrap_or(0), "missing 0x80 marker")),
+                }
+            }
+        }
+    }
+}
+impl Invertible for PadModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.pad_mode = if self.pad { PadMode::Unpad } else { PadMode::Pad };
+        cfg.pad_scheme = self.scheme;
+        cfg.pad_block_size = self.block_size;
+        Ok(MODULE_PAD)
+    }
+}
+impl ByteProcessor for PadModule {
+    fn name(&self) -> &'static str { MODULE_PAD }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if !self.pad {
+            return self.unpad(input);
+        }
+        let mut out = input.to_vec();
+        match self.scheme {
+            PadScheme::Pkcs7 => {
+                let n = self.block_size - input.len() % self.block_size;
+                out.resize(input.len() + n, n as u8);
+            }
+            PadScheme::Zero => {
+                let n = (self.block_size - input.len() % self.block_size) % self.block_size;
+                out.resize(input.len() + n, 0);
+            }
+            PadScheme::Iso7816 => {
+                out.push(0x80);
+                let n = (self.block_size - out.len() % self.block_size) % self.block_size;
+                out.resize(out.len() + n, 0);
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Prepend a fixed header
+#[derive(Debug)]
+pub struct PrependModule {
+    header: Vec<u8>,
+}
+impl PrependModule {
+    pub fn new(hex_header: &str) -> Result<Self, ByteProcError> {
+        let header = Vec::from_hex(hex_header)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        Ok(PrependModule { header })
+    }
+}
+impl ByteProcessor for PrependModule {
+    fn name(&self) -> &'static str { MODULE_PREPEND }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut out = Vec::with_capacity(self.header.len() + input.len());
+        out.extend_from_slice(&self.header);
+        out.extend_from_slice(input);
+        Ok(out)
+    }
+}
+
+/// Append a fixed trailer
+#[derive(Debug)]
+pub struct AppendModule {
+    trailer: Vec<u8>,
+}
+impl AppendModule {
+    pub fn new(hex_trailer: &str) -> Result<Self, ByteProcError> {
+        let trailer = Vec::from_hex(hex_trailer)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        Ok(AppendModule { trailer })
+    }
+}
+impl ByteProcessor for AppendModule {
+    fn name(&self) -> &'static str { MODULE_APPEND }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut out = Vec::with_capacity(input.len() + self.trailer.len());
+        out.extend_from_slice(input);
+        out.extend_from_slice(&self.trailer);
+        Ok(out)
+    }
+}
+
+/// Reverse the byte order of the whole buffer
+#[derive(Debug)]
+pub struct ReverseModule;
+impl Invertible for ReverseModule {
+    fn invert_config(&self, _cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        Ok(MODULE_REVERSE)
+    }
+}
+impl ByteProcessor for ReverseModule {
+    fn name(&self) -> &'static str { MODULE_REVERSE }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        Ok(input.iter().rev().copied().collect())
+    }
+}
+
+/// Swap the endianness of each 16, 32 or 64-bit word
+#[derive(Debug)]
+pub struct SwapModule {
+    word_bytes: usize,
+}
+impl SwapModule {
+    pub fn new(word_bits: u8) -> Result<Self, ByteProcError> {
+        if ![16, 32, 64].contains(&word_bits) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "swap_word_bits must be 16, 32 or 64, got {}",
+                word_bits
+            )));
+        }
+        Ok(SwapModule { word_bytes: word_bits as usize / 8 })
+    }
+}
+impl Invertible for SwapModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.swap_word_bits = (self.word_bytes * 8) as u8;
+        Ok(MODULE_SWAP)
+    }
+}
+impl ByteProcessor for SwapModule {
+    fn name(&self) -> &'static str { MODULE_SWAP }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if !input.len().is_multiple_of(self.word_bytes) {
+            return Err(ByteProcError::InvalidInput {
+                offset: input.len() - input.len() % self.word_bytes,
+                message: format!(
+                    "{} bytes is not a whole number of {}-bit words",
+                    input.len(),
+                    self.word_bytes * 8
+                ),
+            });
+        }
+        Ok(input
+            .chunks_exact(self.word_bytes)
+            .flat_map(|word| word.iter().rev().copied())
+            .collect())
+    }
+}
+
+/// Reverse the bit order within each byte
+#[derive(Debug)]
+pub struct BitReverseModule;
+impl Invertible for BitReverseModule {
+    fn invert_config(&self, _cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        Ok(MODULE_BITREVERSE)
+    }
+}
+impl ByteProcessor for BitReverseModule {
+    fn name(&self) -> &'static str { MODULE_BITREVERSE }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        Ok(input.iter().map(|b| b.reverse_bits()).collect())
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(BytewiseStream { module: self }))
+    }
+}
+
+/// Bitwise operations: AND/OR with a cycling mask, NOT, and rotations or
+/// shifts either per byte or across the whole buffer (MSB first)
+#[derive(Debug, Clone)]
+pub struct BitopsModule {
+    op: BitOp,
+    mask: Vec<u8>,
+    amount: u32,
+    scope: BitScope,
+}
+impl BitopsModule {
+    pub fn new(op: BitOp, hex_mask: Option<&str>, amount: u32, scope: BitScope) -> Result<Self, ByteProcError> {
+        let mask = match hex_mask {
+            Some(m) => Vec::from_hex(m).map_err(|e| ByteProcError::HexDecode(e.to_string()))?,
+            None => Vec::new(),
+        };
+        if matches!(op, BitOp::And | BitOp::Or) && mask.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "bitops_mask must be set for {}",
+                op
+            )));
+        }
+        Ok(BitopsModule { op, mask, amount, scope })
+    }
+
+    /// The operation that undoes this one, if there is one (NOT and rotations)
+    pub fn inverse(&self) -> Option<BitopsModule> {
+        let op = match self.op {
+            BitOp::Not => BitOp::Not,
+            BitOp::Rotl => BitOp::Rotr,
+            BitOp::Rotr => BitOp::Rotl,
+            BitOp::And | BitOp::Or | BitOp::Shl | BitOp::Shr => return None,
+        };
+        Some(BitopsModule { op, ..self.clone() })
+    }
+
+    fn shift_byte(&self, b: u8) -> u8 {
+        match self.op {
+            BitOp::Rotl => b.rotate_left(self.amount % 8),
+            BitOp::Rotr => b.rotate_right(self.amount % 8),
+            BitOp::Shl => b.checked_shl(self.amount).unwrap_or(0),
+            BitOp::Shr => b.checked_shr(self.amount).unwrap_or(0),
+            _ => b,
+        }
+    }
+
+    fn shift_buffer(&self, input: &[u8]) -> Vec<u8> {
+        let mut bits = bytes_to_bits(input, BitOrder::Msb);
+        let n = bits.len();
+        if n == 0 {
+            return Vec::new();
+        }
+        match self.op {
+            BitOp::Rotl => bits.rotate_left(self.amount as usize % n),
+            BitOp::Rotr => bits.rotate_right(self.amount as usize % n),
+            BitOp::Shl => {
+                let k = (self.amount as usize).min(n);
+                bits.drain(..k);
+                bits.resize(n, 0);
+            }
+            BitOp::Shr => {
+                let k = (self.amount as usize).min(n);
+                bits.truncate(n - k);
+                bits.splice(0..0, std::iter::repeat_n(0, k));
+            }
+            _ => {}
+        }
+        bits_to_bytes(&bits, BitOrder::Msb)
+    }
+}
+impl Invertible for BitopsModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        let inverse = self.inverse().ok_or_else(|| {
+            ByteProcError::InvalidConfiguration(format!("bitops {} has no inverse", self.op))
+        })?;
+        cfg.bitops_op = inverse.op;
+        cfg.bitops_mask = (!inverse.mask.is_empty()).then(|| hex::encode(&inverse.mask));
+        cfg.bitops_amount = inverse.amount;
+        cfg.bitops_scope = inverse.scope;
+        Ok(MODULE_BITOPS)
+    }
+}
+impl ByteProcessor for BitopsModule {
+    fn name(&self) -> &'static str { MODULE_BITOPS }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        Ok(match self.op {
+            BitOp::And => input.iter().enumerate().map(|(i, &b)| b & self.mask[i % self.mask.len()]).collect(),
+            BitOp::Or => input.iter().enumerate().map(|(i, &b)| b | self.mask[i % self.mask.len()]).collect(),
+            BitOp::Not => input.iter().map(|&b| !b).collect(),
+            _ => match self.scope {
+                BitScope::Byte => input.iter().map(|&b| self.shift_byte(b)).collect(),
+                BitScope::Buffer => self.shift_buffer(input),
+            },
+        })
+    }
+}
+
+/// Substitution box: replaces every byte `b` with `table[b]`
+#[derive(Debug, Clone)]
+pub struct SboxModule {
+    table: [u8; 256],
+}
+impl SboxModule {
+    /// Build from a 256-entry table, using its inverse when `inverse` is set
+    pub fn new(table: &[u8], inverse: bool) -> Result<Self, ByteProcError> {
+        let table: [u8; 256] = table.try_into().map_err(|_| {
+            ByteProcError::InvalidConfiguration(format!(
+                "sbox table must have 256 entries, got {}",
+                table.len()
+            ))
+        })?;
+        let m = SboxModule { table };
+        if inverse { m.inverse() } else { Ok(m) }
+    }
+
+    /// Build from 512 hex characters (whitespace is ignored)
+    pub fn from_hex(hex_table: &str, inverse: bool) -> Result<Self, ByteProcError> {
+        let compact: String = hex_table.chars().filter(|c| !c.is_whitespace()).collect();
+        let table = Vec::from_hex(&compact)
+            .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
+        Self::new(&table, inverse)
+    }
+
+    /// Build from a file holding the table as hex text
+    pub fn from_file(path: &PathBuf, inverse: bool) -> Result<Self, ByteProcError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ByteProcError::Io(format!("Failed to read sbox table file: {}", e)))?;
+        Self::from_hex(&contents, inverse)
+    }
+
+    /// True when every byte value appears exactly once in the table
+    pub fn is_permutation(&self) -> bool {
+        let mut seen = [false; 256];
+        self.table.iter().all(|&v| !std::mem::replace(&mut seen[v as usize], true))
+    }
+
+    /// Inverse table; fails if the table is not a permutation
+    pub fn inverse(&self) -> Result<SboxModule, ByteProcError> {
+        if !self.is_permutation() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "sbox table is not a permutation, it has no inverse".into(),
+            ));
+        }
+        let mut table = [0u8; 256];
+        for (i, &v) in self.table.iter().enumerate() {
+            table[v as usize] = i as u8;
+        }
+        Ok(SboxModule { table })
+    }
+}
+impl Invertible for SboxModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        // The table as built, inverted again when the module is created
+        self.inverse()?;
+        cfg.sbox_table = Some(hex::encode(self.table));
+        cfg.sbox_table_file = None;
+        cfg.sbox_mode = SboxMode::Inverse;
+        Ok(MODULE_SBOX)
+    }
+}
+impl ByteProcessor for SboxModule {
+    fn name(&self) -> &'static str { MODULE_SBOX }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        Ok(input.iter().map(|&b| self.table[b as usize]).collect())
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(BytewiseStream { module: self }))
+    }
+}
+
+/// Byte-level run-length coding (PackBits)
+///
+/// A header byte `n` in 0..=127 is followed by `n + 1` literal bytes, `n` in
+/// -127..=-1 (as i8) is followed by one byte repeated `1 - n` times, and
+/// -128 is a no-op.
+#[derive(Debug)]
+pub struct RleModule {
+    encode: bool,
+}
+impl RleModule {
+    pub fn new(encode: bool) -> Self {
+        RleModule { encode }
+    }
+
+    fn encode(input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(input.len() + input.len() / 128 + 1);
+        Self::encode_packets(input, &mut out, true);
+        out
+    }
+
+    /// Encode packets from the front of `input` and return how many bytes
+    /// they used. Unless `last` is set, stops while the next packet could
+    /// still depend on bytes that have not arrived yet, so streamed output
+    /// matches whole-buffer output.
+    fn encode_packets(input: &[u8], out: &mut Vec<u8>, last: bool) -> usize {
+        // A literal packet looks at most 128 + 128 bytes ahead
+        const LOOKAHEAD: usize = 256;
+        let run_at = |i: usize| {
+            input[i..].iter().take(128).take_while(|&&b| b == input[i]).count()
+        };
+        let mut i = 0;
+        while i < input.len() && (last || input.len() - i > LOOKAHEAD) {
+            let run = run_at(i);
+            if run >= 3 {
+                out.push((1 - run as i16) as u8);
+                out.push(input[i]);
+                i += run;
+                continue;
+            }
+            // Literal packet up to the next run of three or more
+            let start = i;
+            while i < input.len() && i - start < 128 && run_at(i) < 3 {
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&input[start..i]);
+        }
+        i
+    }
+
+    fn decode(input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut out = Vec::with_capacity(input.len() * 2);
+        let used = Self::decode_packets(input, &mut out);
+        if used < input.len() {
+            return Err(ByteProcError::InvalidInput {
+                offset: used,
+                message: if (input[used] as i8) >= 0 {
+                    "rle literal run is truncated".into()
+                } else {
+                    "rle repeat run is missing its byte".into()
+                },
+            });
+        }
+        Ok(out)
+    }
+
+    /// Decode complete packets from the front of `input` and return how
+    /// many bytes they used; a truncated last packet is left unread
+    fn decode_packets(input: &[u8], out: &mut Vec<u8>) -> usize {
+        let mut i = 0;
+        while i < input.len() {
+            let header = input[i] as i8;
+            match header {
+                0..=127 => {
+                    let n = header as usize + 1;
+                    let Some(literal) = input.get(i + 1..i + 1 + n) else { break };
+                    out.extend_from_slice(literal);
+                    i += 1 + n;
+                }
+                -128 => i += 1,
+                _ => {
+                    let Some(&b) = input.get(i + 1) else { break };
+                    out.resize(out.len() + (1 - header as isize) as usize, b);
+                    i += 2;
+                }
+            }
+        }
+        i
+    }
+}
+impl Invertible for RleModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.rle_mode = if self.encode { RleMode::Decode } else { RleMode::Encode };
+        Ok(MODULE_RLE)
+    }
+}
+impl ByteProcessor for RleModule {
+    fn name(&self) -> &'static str { MODULE_RLE }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.encode {
+            Ok(Self::encode(input))
+        } else {
+            Self::decode(input)
+        }
+    }
+    fn stream(&self) -> Option<Box<dyn StreamProcessor + '_>> {
+        Some(Box::new(RleStream { encode: self.encode, pending: Vec::new() }))
+    }
+}
+
+/// Longest Huffman code the encoder produces and the decoder accepts
+const HUFFMAN_MAX_CODE_LEN: u8 = 32;
+
+/// Static Huffman coding with the code table embedded in the output
+///
+/// Output layout: original length (u32, big endian), symbol count (u16, big
+/// endian), one `(symbol, code length)` byte pair per symbol, then the
+/// canonical Huffman bit stream MSB first, zero padded to a whole byte.
+#[derive(Debug)]
+pub struct HuffmanModule {
+    encode: bool,
+}
+impl HuffmanModule {
+    pub fn new(encode: bool) -> Self {
+        HuffmanModule { encode }
+    }
+
+    /// Code length for every symbol that occurs, limited to
+    /// `HUFFMAN_MAX_CODE_LEN` by flattening the frequencies until it fits
+    fn code_lengths(input: &[u8]) -> Vec<(u8, u8)> {
+        let mut freq = [0u64; 256];
+        for &b in input {
+            freq[b as usize] += 1;
+        }
+        loop {
+            let symbols: Vec<usize> = (0..256).filter(|&s| freq[s] > 0).collect();
+            if symbols.len() <= 1 {
+                return symbols.into_iter().map(|s| (s as u8, 1)).collect();
+            }
+
+            let mut parent = vec![usize::MAX; 2 * symbols.len() - 1];
+            let mut heap: BinaryHeap<Reverse<(u64, usize)>> = symbols
+                .iter()
+                .enumerate()
+                .map(|(node, &s)| Reverse((freq[s], node)))
+                .collect();
+            let mut next = symbols.len();
+            while let (Some(Reverse((fa, a))), Some(Reverse((fb, b)))) = (heap.pop(), heap.pop()) {
+                parent[a] = next;
+                parent[b] = next;
+                heap.push(Reverse((fa + fb, next)));
+                next += 1;
+            }
+
+            let lengths: Vec<(u8, u8)> = symbols
+                .iter()
+                .enumerate()
+                .map(|(node, &s)| {
+                    let mut depth = 0u32;
+                    let mut n = node;
+                    while parent[n] != usize::MAX {
+                        n = parent[n];
+                        depth += 1;
+                    }
+                    (s as u8, depth.min(u8::MAX as u32) as u8)
+                })
+                .collect();
+            if lengths.iter().all(|&(_, len)| len <= HUFFMAN_MAX_CODE_LEN) {
+                return lengths;
+            }
+            for f in freq.iter_mut().filter(|f| **f > 0) {
+                *f = f.div_ceil(2);
+            }
+        }
+    }
+
+    /// Sort `(symbol, length)` pairs into canonical order
+    fn canonical_order(lengths: &mut [(u8, u8)]) {
+        lengths.sort_by_key(|&(s, len)| (len, s));
+    }
+
+    fn encode(input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let len = u32::try_from(input.len())
+            .map_err(|_| ByteProcError::Module("huffman input larger than 4 GiB".into()))?;
+        let mut lengths = Self::code_lengths(input);
+        Self::canonical_order(&mut lengths);
+
+        let mut out = Vec::with_capacity(6 + lengths.len() * 2 + input.len());
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&(lengths.len() as u16).to_be_bytes());
+        let mut codes = [(0u64, 0u8); 256];
+        let mut code = 0u64;
+        let mut prev_len = 0u8;
+        for &(s, l) in &lengths {
+            out.push(s);
+            out.push(l);
+            code <<= l - prev_len;
+            codes[s as usize] = (code, l);
+            code += 1;
+            prev_len = l;
+        }
+
+        let mut bits = Vec::with_capacity(input.len() * 8);
+        for &b in input {
+            let (code, l) = codes[b as usize];
+            for i in (0..l).rev() {
+                bits.push(((code >> i) & 1) as u8);
+            }
+        }
+        out.extend(bits_to_bytes(&bits, BitOrder::Msb));
+        Ok(out)
+    }
+
+    fn decode(input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let malformed = |offset: usize, why: String| ByteProcError::InvalidInput {
+            offset,
+            message: format!("malformed huffman data: {}", why),
+        };
+        if input.len() < 6 {
+            return Err(malformed(input.len(), format!("header needs 6 bytes, got {}", input.len())));
+        }
+        let out_len = u32::from_be_bytes([input[0], input[1], input[2], input[3]]) as usize;
+        let count = u16::from_be_bytes([input[4], input[5]]) as usize;
+        if count > 256 {
+            return Err(malformed(4, format!("{} symbols in code table", count)));
+        }
+        let table = input
+            .get(6..6 + count * 2)
+            .ok_or_else(|| malformed(input.len(), "code table is truncated".into()))?;
+
+        let mut lengths: Vec<(u8, u8)> = table.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+        let mut seen = [false; 256];
+        let mut kraft = 0u64;
+        for (i, &(s, l)) in lengths.iter().enumerate() {
+            if l == 0 || l > HUFFMAN_MAX_CODE_LEN {
+                return Err(malformed(6 + i * 2 + 1, format!("code length {} for symbol 0x{:02x}", l, s)));
+            }
+            if std::mem::replace(&mut seen[s as usize], true) {
+                return Err(malformed(6 + i * 2, format!("symbol 0x{:02x} listed twice", s)));
+            }
+            kraft += 1u64 << (HUFFMAN_MAX_CODE_LEN - l);
+        }
+        if kraft > 1u64 << HUFFMAN_MAX_CODE_LEN {
+            return Err(malformed(6, "code lengths are over-subscribed".into()));
+        }
+        if out_len > 0 && lengths.is_empty() {
+            return Err(malformed(4, "empty code table for non-empty data".into()));
+        }
+        Self::canonical_order(&mut lengths);
+        let mut counts = [0u64; HUFFMAN_MAX_CODE_LEN as usize + 1];
+        for &(_, l) in &lengths {
+            counts[l as usize] += 1;
+        }
+
+        let stream_start = 6 + count * 2;
+        let stream = &input[stream_start..];
+        let total_bits = stream.len() * 8;
+        let mut out = Vec::with_capacity(out_len.min(total_bits));
+        let mut pos = 0usize;
+        while out.len() < out_len {
+            // Canonical decode, one bit at a time (see zlib's puff.c)
+            let (mut code, mut first, mut index) = (0u64, 0u64, 0u64);
+            let mut symbol = None;
+            for &count in &counts[1..] {
+                if pos >= total_bits {
+                    return Err(malformed(input.len(), format!(
+                        "bit stream ends after {} of {} bytes",
+                        out.len(),
+                        out_len
+                    )));
+                }
+                code |= ((stream[pos / 8] >> (7 - pos % 8)) & 1) as u64;
+                pos += 1;
+                if code - first < count {
+                    symbol = Some(lengths[(index + code - first) as usize].0);
+                    break;
+                }
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            match symbol {
+                Some(s) => out.push(s),
+                None => return Err(malformed(stream_start + (pos - 1) / 8, format!("invalid code at bit {}", pos))),
+            }
+        }
+        Ok(out)
+    }
+}
+impl Invertible for HuffmanModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.huffman_mode = if self.encode { HuffmanMode::Decode } else { HuffmanMode::Encode };
+        Ok(MODULE_HUFFMAN)
+    }
+}
+impl ByteProcessor for HuffmanModule {
+    fn name(&self) -> &'static str { MODULE_HUFFMAN }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.encode {
+            Self::encode(input)
+        } else {
+            Self::decode(input)
+        }
+    }
+}
+
+/// Repetition code: repeat each bit or byte `count` times on encode and
+/// majority-vote each group on decode (bit by bit, also for byte units)
+#[derive(Debug)]
+pub struct RepetitionModule {
+    encode: bool,
+    count: usize,
+    unit: RepetitionUnit,
+}
+impl RepetitionModule {
+    pub fn new(encode: bool, count: usize, unit: RepetitionUnit) -> Result<Self, ByteProcError> {
+        if count == 0 || count.is_multiple_of(2) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "repetition_count must be odd so the majority vote cannot tie, got {}",
+                count
+            )));
+        }
+        Ok(RepetitionModule { encode, count, unit })
+    }
+
+    /// Majority vote over `count` copies of each symbol. Returns the voted
+    /// symbols and the number of copies (bits) that disagreed with the vote.
+    fn vote(&self, copies: &[u8], bits_per_symbol: usize) -> (Vec<u8>, usize) {
+        let mut corrected = 0usize;
+        let voted = copies
+            .chunks_exact(self.count)
+            .map(|group| {
+                let mut value = 0u8;
+                for bit in 0..bits_per_symbol {
+                    let ones = group.iter().filter(|&&c| (c >> bit) & 1 == 1).count();
+                    let majority = ones * 2 > self.count;
+                    corrected += if majority { self.count - ones } else { ones };
+                    value |= (majority as u8) << bit;
+                }
+                value
+            })
+            .collect();
+        (voted, corrected)
+    }
+}
+impl Invertible for RepetitionModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.repetition_mode = if self.encode { RepetitionMode::Decode } else { RepetitionMode::Encode };
+        cfg.repetition_count = self.count;
+        cfg.repetition_unit = self.unit;
+        Ok(MODULE_REPETITION)
+    }
+}
+impl ByteProcessor for RepetitionModule {
+    fn name(&self) -> &'static str { MODULE_REPETITION }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.process_with_context(input, &mut ProcessContext::default())
+    }
+    fn process_with_context(&self, input: &[u8], ctx: &mut ProcessContext) -> Result<Vec<u8>, ByteProcError> {
+        let n = self.count;
+        if self.encode {
+            return Ok(match self.unit {
+                RepetitionUnit::Byte => input.iter().flat_map(|&b| std::iter::repeat_n(b, n)).collect(),
+                RepetitionUnit::Bit => {
+                    let bits = bytes_to_bits(input, BitOrder::Msb);
+                    let repeated: Vec<u8> = bits.iter().flat_map(|&b| std::iter::repeat_n(b, n)).collect();
+                    bits_to_bytes(&repeated, BitOrder::Msb)
+                }
+            });
+        }
+
+        if !input.len().is_multiple_of(n) {
+            return Err(ByteProcError::InvalidInput {
+                offset: input.len() - input.len() % n,
+                message: format!("repetition decode needs a multiple of {} bytes, got {}", n, input.len()),
+            });
+        }
+        let (out, corrected) = match self.unit {
+            RepetitionUnit::Byte => self.vote(input, 8),
+            RepetitionUnit::Bit => {
+                let (bits, corrected) = self.vote(&bytes_to_bits(input, BitOrder::Msb), 1);
+                (bits_to_bytes(&bits, BitOrder::Msb), corrected)
+            }
+        };
+        info!(
+            "[{}] Repetition decode (n={}, {}) corrected {} disagreeing bit(s)",
+            make_instance_id(), n, self.unit, corrected
+        );
+        ctx.set("repetition.corrected_bits", corrected);
+        Ok(out)
+    }
+}
+
+/// Group the bit stream into k-bit symbols, one symbol index per output
+/// byte, optionally Gray coded so neighbouring constellation points differ
+/// in a single bit. Demap reverses both steps.
+#[derive(Debug)]
+pub struct SymbolsModule {
+    map: bool,
+    gray: bool,
+    bits: BitsModule,
+}
+impl SymbolsModule {
+    pub fn new(map: bool, bits_per_symbol: u8, gray: bool, order: BitOrder) -> Result<Self, ByteProcError> {
+        let bits = BitsModule::new(map, bits_per_symbol, order).map_err(|_| {
+            ByteProcError::InvalidConfiguration(format!(
+                "symbols_bits must be between 1 and 8, got {}",
+                bits_per_symbol
+            ))
+        })?;
+        Ok(SymbolsModule { map, gray, bits })
+    }
+}
+impl Invertible for SymbolsModule {
+    fn invert_config(&self, cfg: &mut Config) -> Result<&'static str, ByteProcError> {
+        cfg.symbols_mode = if self.map { SymbolsMode::Demap } else { SymbolsMode::Map };
+        cfg.symbols_bits = self.bits.bits_per_byte as u8;
+        cfg.symbols_gray = self.gray;
+        cfg.symbols_order = self.bits.order;
+        Ok(MODULE_SYMBOLS)
+    }
+}
+impl ByteProcessor for SymbolsModule {
+    fn name(&self) -> &'static str { MODULE_SYMBOLS }
+    fn as_invertible(&self) -> Option<&dyn Invertible> { Some(self) }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.map {
+            let mut symbols = self.bits.process(input)?;
+            if self.gray {
+                symbols.iter_mut().for_each(|s| *s = gray_encode(*s));
+            }
+            Ok(symbols)
+        } else if self.gray {
+            let symbols: Vec<u8> = input.iter().map(|&s| gray_decode(s)).collect();
+            self.bits.process(&symbols)
+        } else {
+            self.bits.process(input)
+        }
+    }
+}
+
+/// Resource limits for a script run
+#[derive(Debug, Clone)]
+pub struct ScriptLimits {
+    /// Maximum number of script operations per message
+    pub max_operations: u64,
+    /// Cap on each blob or string (bytes) and each array or map (elements)
+    /// the script builds. Memory across objects is not capped.
+    pub max_size: usize,
+    /// Wall-clock limit per message
+    pub timeout: Duration,
+}
+
+/// Runs a user-supplied Rhai script defining `fn process(bytes)`. The
+/// argument is a blob; the script returns a blob or an array of integers
+/// in 0..=255. Scripts have no file or network access and `print` goes to
+/// the log.
+pub struct ScriptModule {
+    name: String,
+    engine: rhai::Engine,
+    ast: rhai::AST,
+    deadline: Rc<Cell<Option<Instant>>>,
+    timeout: Duration,
+}
+impl ScriptModule {
+    /// Compile `source`; `name` identifies the script in error messages
+    pub fn new(source: &str, name: &str, limits: ScriptLimits) -> Result<Self, ByteProcError> {
+        let mut engine = rhai::Engine::new();
+        // No `import` of script files from disk
+        engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+        engine.set_max_operations(limits.max_operations);
+        engine.set_max_array_size(limits.max_size);
+        engine.set_max_string_size(limits.max_size);
+        engine.set_max_map_size(limits.max_size);
+
+        let deadline: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+        let progress_deadline = deadline.clone();
+        engine.on_progress(move |_| match progress_deadline.get() {
+            Some(deadline) if Instant::now() > deadline => Some("timeout".into()),
+            _ => None,
+        });
+        let print_name = name.to_string();
+        engine.on_print(move |s| info!("[{}] script {}: {}", make_instance_id(), print_name, s));
+
+        let ast = engine.compile(source).map_err(|e| {
+            ByteProcError::Module(format!("script {} line {}: {}", name, e.1.line().unwrap_or(0), e.0))
+        })?;
+        if !ast.iter_functions().any(|f| f.name == "process" && f.params.len() == 1) {
+            return Err(ByteProcError::Module(format!(
+                "script {} must define fn process(bytes)",
+                name
+            )));
+        }
+
+        Ok(ScriptModule { name: name.to_string(), engine, ast, deadline, timeout: limits.timeout })
+    }
+
+    pub fn from_file(path: &PathBuf, limits: ScriptLimits) -> Result<Self, ByteProcError> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| ByteProcError::Io(format!("Failed to read script file: {}", e)))?;
+        Self::new(&source, &path.display().to_string(), limits)
+    }
+
+    /// Module error pointing at the script line where evaluation failed
+    fn script_error(&self, mut err: rhai::EvalAltResult) -> ByteProcError {
+        while let rhai::EvalAltResult::ErrorInFunctionCall(_, _, inner, _) = err {
+            err = *inner;
+        }
+        if let rhai::EvalAltResult::ErrorTerminated(..) = err {
+            return ByteProcError::Module(format!(
+                "script {} exceeded its {} ms time limit",
+                self.name,
+                self.timeout.as_millis()
+            ));
+        }
+        match err.take_position().line() {
+            Some(line) => ByteProcError::Module(format!("script {} line {}: {}", self.name, line, err)),
+            None => ByteProcError::Module(format!("script {}: {}", self.name, err)),
+        }
+    }
+}
+impl ByteProcessor for ScriptModule {
+    fn name(&self) -> &'static str { MODULE_SCRIPT }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.deadline.set(Some(Instant::now() + self.timeout));
+        let result = self.engine.call_fn::<rhai::Dynamic>(
+            &mut rhai::Scope::new(),
+            &self.ast,
+            "process",
+            (rhai::Blob::from(input),),
+        );
+        self.deadline.set(None);
+        let result = result.map_err(|e| self.script_error(*e))?;
+
+        if result.is_blob() {
+            return Ok(result.cast::<rhai::Blob>());
+        }
+        let type_name = result.type_name();
+        let values = result.try_cast::<rhai::Array>().ok_or_else(|| {
+            ByteProcError::Module(format!(
+                "script {} returned {}, expected a blob or an array of bytes",
+                self.name, type_name
+            ))
+        })?;
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                v.as_int()
+                    .ok()
+                    .and_then(|n| u8::try_from(n).ok())
+                    .ok_or_else(|| {
+                        ByteProcError::Module(format!(
+                            "script {} returned a non-byte value at index {}",
+                            self.name, i
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Resource limits for a WebAssembly module run
+#[derive(Debug, Clone)]
+pub struct WasmLimits {
+    /// Fuel per message, roughly one unit per executed instruction
+    pub fuel: u64,
+    /// Maximum linear memory in bytes
+    pub max_memory: usize,
+}
+
+/// Runs an untrusted WebAssembly transform in a sandbox. The module may not
+/// import anything and must export:
+/// - `memory`
+/// - `alloc(len: i32) -> i32`, the address to write the input to
+/// - `process(ptr: i32, len: i32) -> i64`, returning `(out_ptr << 32) | out_len`,
+///   or a negative value to report an error
+///
+/// Every message runs in a fresh instance, so no state carries over.
+pub struct WasmModule {
+    name: String,
+    engine: wasmi::Engine,
+    module: wasmi::Module,
+    limits: WasmLimits,
+}
+
+/// One instantiated copy of a `WasmModule`
+struct WasmInstance {
+    store: wasmi::Store<wasmi::StoreLimits>,
+    memory: wasmi::Memory,
+    alloc: wasmi::TypedFunc<i32, i32>,
+    process: wasmi::TypedFunc<(i32, i32), i64>,
+}
+
+impl WasmModule {
+    /// Compile `wasm`; `name` identifies the module in error messages
+    pub fn new(wasm: &[u8], name: &str, limits: WasmLimits) -> Result<Self, ByteProcError> {
+        let fail = |msg: String| ByteProcError::InvalidConfiguration(format!("wasm {}: {}", name, msg));
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = wasmi::Engine::new(&config);
+        let module = wasmi::Module::new(&engine, wasm).map_err(|e| fail(e.to_string()))?;
+        if let Some(import) = module.imports().next() {
+            return Err(fail(format!(
+                "imports are not allowed, found {}::{}",
+                import.module(),
+                import.name()
+            )));
+        }
+
+        let wasm = WasmModule { name: name.to_string(), engine, module, limits };
+        // Catch missing exports and oversized memory up front
+        wasm.instantiate().map_err(fail)?;
+        Ok(wasm)
+    }
+
+    pub fn from_file(path: &PathBuf, limits: WasmLimits) -> Result<Self, ByteProcError> {
+        let wasm = std::fs::read(path)
+            .map_err(|e| ByteProcError::Io(format!("Failed to read wasm file: {}", e)))?;
+        Self::new(&wasm, &path.display().to_string(), limits)
+    }
+
+    fn instantiate(&self) -> Result<WasmInstance, String> {
+        let limits = wasmi::StoreLimitsBuilder::new().memory_size(self.limits.max_memory).build();
+        let mut store = wasmi::Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.limits.fuel).map_err(|e| e.to_string())?;
+        let instance = wasmi::Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| "missing memory export".to_string())?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| format!("alloc export: {}", e))?;
+        let process = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "process")
+            .map_err(|e| format!("process export: {}", e))?;
+        Ok(WasmInstance { store, memory, alloc, process })
+    }
+
+    /// Module error for a failed call, calling out fuel exhaustion
+    fn call_error(&self, err: wasmi::Error) -> ByteProcError {
+        if err.as_trap_code() == Some(wasmi::core::TrapCode::OutOfFuel) {
+            return ByteProcError::Module(format!(
+                "wasm {} ran out of fuel ({} units)",
+                self.name, self.limits.fuel
+            ));
+        }
+        ByteProcError::Module(format!("wasm {} trapped: {}", self.name, err))
+    }
+}
+impl ByteProcessor for WasmModule {
+    fn name(&self) -> &'static str { MODULE_WASM }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut wasm = self
+            .instantiate()
+            .map_err(|e| ByteProcError::Module(format!("wasm {}: {}", self.name, e)))?;
+        let len = i32::try_from(input.len()).map_err(|_| {
+            ByteProcError::Module(format!("input of {} bytes is too large for wasm", input.len()))
+        })?;
+
+        let ptr = wasm.alloc.call(&mut wasm.store, len).map_err(|e| self.call_error(e))?;
+        wasm.memory.write(&mut wasm.store, ptr as u32 as usize, input).map_err(|_| {
+            ByteProcError::Module(format!("wasm {} alloc returned an out-of-bounds address", self.name))
+        })?;
+
+        let packed = wasm.process.call(&mut wasm.store, (ptr, len)).map_err(|e| self.call_error(e))?;
+        if packed < 0 {
+            return Err(ByteProcError::Module(format!(
+                "wasm {} reported error {}",
+                self.name, packed
+            )));
+        }
+        let out_ptr = (packed >> 32) as usize;
+        let out_len = (packed & 0xffff_ffff) as usize;
+        wasm.memory
+            .data(&wasm.store)
+            .get(out_ptr..out_ptr + out_len)
+            .map(|out| out.to_vec())
+            .ok_or_else(|| {
+                ByteProcError::Module(format!(
+                    "wasm {} returned {} bytes at 0x{:x}, outside its memory",
+                    self.name, out_len, out_ptr
+                ))
+            })
+    }
+}
+
+// -------------- Streaming --------------
+
+/// Streams any module by buffering the whole message and processing it in
+/// `finish`. Refuses to hold more than `max_size` bytes.
+pub struct BufferedStream<'a> {
+    module: &'a dyn ByteProcessor,
+    buffer: Vec<u8>,
+    max_size: usize,
+}
+impl<'a> BufferedStream<'a> {
+    pub fn new(module: &'a dyn ByteProcessor, max_size: usize) -> Self {
+        BufferedStream { module, buffer: Vec::new(), max_size }
+    }
+}
+impl StreamProcessor for BufferedStream<'_> {
+    fn name(&self) -> &'static str { self.module.name() }
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        if self.buffer.len() + chunk.len() > self.max_size {
+            return Err(ByteProcError::MaxSizeExceeded(self.max_size, self.buffer.len() + chunk.len()));
+        }
+        self.buffer.extend_from_slice(chunk);
+        Ok(Vec::new())
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        self.module.process(&std::mem::take(&mut self.buffer))
+    }
+}
+
+/// Streams modules whose output for each byte depends only on that byte
+pub struct BytewiseStream<'a> {
+    module: &'a dyn ByteProcessor,
+}
+impl StreamProcessor for BytewiseStream<'_> {
+    fn name(&self) -> &'static str { self.module.name() }
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.module.process(chunk)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        Ok(Vec::new())
+    }
+}
+
+/// XOR with the key position carried across chunks
+pub struct XorStream<'a> {
+    key: &'a [u8],
+    position: usize,
+}
+impl StreamProcessor for XorStream<'_> {
+    fn name(&self) -> &'static str { MODULE_XOR }
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let out = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| b ^ self.key[(self.position + i) % self.key.len()])
+            .collect();
+        self.position = (self.position + chunk.len()) % self.key.len();
+        Ok(out)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Base64 that carries partial 3-byte (encode) or 4-character (decode)
+/// groups over to the next chunk
+pub struct Base64Stream<'a> {
+    module: &'a Base64Module,
+    carry: Vec<u8>,
+}
+impl StreamProcessor for Base64Stream<'_> {
+    fn name(&self) -> &'static str { MODULE_BASE64 }
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.carry.extend_from_slice(chunk);
+        let group = if self.module.encode { 3 } else { 4 };
+        let ready = self.carry.len() / group * group;
+        let rest = self.carry.split_off(ready);
+        let out = self.module.process(&self.carry)?;
+        self.carry = rest;
+        Ok(out)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        self.module.process(&std::mem::take(&mut self.carry))
+    }
+}
+
+/// PackBits that holds back only the bytes the next packet may depend on
+pub struct RleStream {
+    encode: bool,
+    pending: Vec<u8>,
+}
+impl StreamProcessor for RleStream {
+    fn name(&self) -> &'static str { MODULE_RLE }
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        self.pending.extend_from_slice(chunk);
+        let mut out = Vec::new();
+        let used = if self.encode {
+            RleModule::encode_packets(&self.pending, &mut out, false)
+        } else {
+            RleModule::decode_packets(&self.pending, &mut out)
+        };
+        self.pending.drain(..used);
+        Ok(out)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        RleModule { encode: self.encode }.process(&std::mem::take(&mut self.pending))
+    }
+}
+
+/// A chain of stream processors fed one chunk at a time
+pub struct StreamPipeline<'a> {
+    stages: Vec<Box<dyn StreamProcessor + 'a>>,
+}
+impl StreamPipeline<'_> {
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut data = chunk.to_vec();
+        for stage in &mut self.stages {
+            data = stage.update(&data)?;
+        }
+        Ok(data)
+    }
+
+    /// Flush every stage, pushing what each one held back through the rest
+    pub fn finish(&mut self) -> Result<Vec<u8>, ByteProcError> {
+        let mut data = Vec::new();
+        for stage in &mut self.stages {
+            let mut out = stage.update(&data)?;
+            out.extend(stage.finish()?);
+            data = out;
+        }
+        Ok(data)
+    }
+}
+
+// -------------- Plugins --------------
+
+/// Version of the plugin ABI. Bump it on any change to `PluginDescriptor`,
+/// `PluginBuffer` or the meaning of their fields.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin exports: `extern "C" fn() -> *const PluginDescriptor`
+pub const PLUGIN_ENTRY_SYMBOL: &str = "byteproc_plugin";
+
+/// Bytes handed from a plugin to byteproc. Byteproc copies them and gives
+/// the buffer back to the plugin through `free_buffer`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct PluginBuffer {
+    pub ptr: *mut u8,
+    pub len: usize,
+    pub cap: usize,
+}
+impl PluginBuffer {
+    pub fn empty() -> Self {
+        PluginBuffer { ptr: std::ptr::null_mut(), len: 0, cap: 0 }
+    }
+
+    /// For plugins written in Rust: hand a vector over to byteproc
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        let mut data = std::mem::ManuallyDrop::new(data);
+        PluginBuffer { ptr: data.as_mut_ptr(), len: data.len(), cap: data.capacity() }
+    }
+
+    /// For plugins written in Rust: take back a buffer made by `from_vec`
+    ///
+    /// # Safety
+    /// The buffer must come from `PluginBuffer::from_vec` in the same library.
+    pub unsafe fn into_vec(self) -> Vec<u8> {
+        if self.ptr.is_null() {
+            return Vec::new();
+        }
+        unsafe { Vec::from_raw_parts(self.ptr, self.len, self.cap) }
+    }
+}
+
+/// Entry points of a plugin. `abi_version` must stay the first field so
+/// plugins built against another ABI are rejected before anything else is
+/// read.
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub abi_version: u32,
+    /// Module name used in `pipeline`, NUL terminated
+    pub name: *const c_char,
+    /// JSON Schema of the plugin config, NUL terminated, may be null
+    pub config_schema: *const c_char,
+    /// Create an instance from its JSON config. Returns null on failure,
+    /// optionally with a message in `error`.
+    pub create: unsafe extern "C" fn(config: *const u8, config_len: usize, error: *mut PluginBuffer) -> *mut c_void,
+    /// Process one message. Returns 0 with the result in `output`, or
+    /// non-zero with an error message in `output`.
+    pub process: unsafe extern "C" fn(
+        instance: *mut c_void,
+        input: *const u8,
+        input_len: usize,
+        output: *mut PluginBuffer,
+    ) -> i32,
+    /// Release a buffer returned by `create` or `process`
+    pub free_buffer: unsafe extern "C" fn(buffer: PluginBuffer),
+    /// Release an instance returned by `create`
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+}
+
+/// A loaded plugin library. The library stays loaded while any clone of
+/// it or any instance created from it is alive.
+#[derive(Clone)]
+pub struct PluginLibrary {
+    name: &'static str,
+    schema: Option<serde_json::Value>,
+    descriptor: *const PluginDescriptor,
+    _library: Rc<libloading::Library>,
+}
+impl PluginLibrary {
+    pub fn load(path: &Path) -> Result<Self, ByteProcError> {
+        let fail = |msg: String| {
+            ByteProcError::InvalidConfiguration(format!("plugin {}: {}", path.display(), msg))
+        };
+        // SAFETY: loading runs the library's initialisers; plugins in
+        // plugin_dir are trusted the same way as the byteproc binary itself
+        let library = unsafe { libloading::Library::new(path) }.map_err(|e| fail(e.to_string()))?;
+        let descriptor = unsafe {
+            let entry = library
+                .get::<unsafe extern "C" fn() -> *const PluginDescriptor>(PLUGIN_ENTRY_SYMBOL.as_bytes())
+                .map_err(|e| fail(e.to_string()))?;
+            entry()
+        };
+        if descriptor.is_null() {
+            return Err(fail("entry point returned no descriptor".into()));
+        }
+
+        let (name, schema) = unsafe { read_descriptor(&*descriptor) }.map_err(fail)?;
+        // Module names are 'static; a plugin is loaded once per process
+        let name: &'static str = Box::leak(name.into_boxed_str());
+        Ok(PluginLibrary { name, schema, descriptor, _library: Rc::new(library) })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn config_schema(&self) -> Option<&serde_json::Value> {
+        self.schema.as_ref()
+    }
+
+    /// Create a module instance; a missing config is passed as `{}`
+    pub fn instantiate(&self, config: Option<&serde_json::Value>) -> Result<PluginModule, ByteProcError> {
+        let config = config.cloned().unwrap_or_else(|| serde_json::json!({}));
+        self.check_config(&config)?;
+        let json = config.to_string();
+
+        let mut error = PluginBuffer::empty();
+        let instance = unsafe { ((*self.descriptor).create)(json.as_ptr(), json.len(), &mut error) };
+        let message = self.take_buffer(error);
+        if instance.is_null() {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "plugin {} rejected its config: {}",
+                self.name,
+                String::from_utf8_lossy(&message)
+            )));
+        }
+        Ok(PluginModule { plugin: self.clone(), instance })
+    }
+
+    /// Check the config against the `required` and `properties.*.type`
+    /// parts of the plugin's schema
+    fn check_config(&self, config: &serde_json::Value) -> Result<(), ByteProcError> {
+        let fail = |msg: String| {
+            ByteProcError::InvalidConfiguration(format!("plugin_config.{}: {}", self.name, msg))
+        };
+        let object = config.as_object().ok_or_else(|| fail("must be a JSON object".into()))?;
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+
+        let required = schema.get("required").and_then(|r| r.as_array());
+        for key in required.into_iter().flatten().filter_map(|k| k.as_str()) {
+            if !object.contains_key(key) {
+                return Err(fail(format!("missing required key {}", key)));
+            }
+        }
+
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, value) in object {
+            let expected = properties
+                .and_then(|p| p.get(key))
+                .and_then(|p| p.get("type"))
+                .and_then(|t| t.as_str());
+            let matches = match expected {
+                Some("integer") => value.is_i64() || value.is_u64(),
+                Some("number") => value.is_number(),
+                Some("string") => value.is_string(),
+                Some("boolean") => value.is_boolean(),
+                Some("array") => value.is_array(),
+                Some("object") => value.is_object(),
+                _ => true,
+            };
+            if !matches {
+                return Err(fail(format!("{} must be of type {}", key, expected.unwrap_or_default())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a plugin buffer and hand it back to the plugin
+    fn take_buffer(&self, buffer: PluginBuffer) -> Vec<u8> {
+        if buffer.ptr.is_null() {
+            return Vec::new();
+        }
+        let data = unsafe { std::slice::from_raw_parts(buffer.ptr, buffer.len) }.to_vec();
+        unsafe { ((*self.descriptor).free_buffer)(buffer) };
+        data
+    }
+}
+
+/// Check that a plugin descriptor was built for this ABI version, then
+/// read its name and config schema
+///
+/// # Safety
+/// `name` and `config_schema` must be null or point to NUL-terminated
+/// strings; they are only read once the ABI version matches.
+pub unsafe fn read_descriptor(d: &PluginDescriptor) -> Result<(String, Option<serde_json::Value>), String> {
+    if d.abi_version != PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "built for plugin ABI version {}, this byteproc supports version {}",
+            d.abi_version, PLUGIN_ABI_VERSION
+        ));
+    }
+    let name = plugin_str(d.name).ok_or("descriptor has no name")?;
+    let schema = match plugin_str(d.config_schema) {
+        Some(text) => Some(serde_json::from_str(&text).map_err(|e| format!("invalid config schema: {}", e))?),
+        None => None,
+    };
+    Ok((name, schema))
+}
+
+/// Read a NUL-terminated string owned by a plugin
+fn plugin_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+}
+
+/// Load every plugin library in `dir`. Plugin names must be unique and
+/// must not shadow a built-in module.
+pub fn load_plugins(dir: &Path) -> Result<Vec<PluginLibrary>, ByteProcError> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| ByteProcError::Io(format!("Failed to read plugin_dir {}: {}", dir.display(), e)))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+        .collect();
+    paths.sort();
+
+    let mut plugins: Vec<PluginLibrary> = Vec::new();
+    for path in paths {
+        let plugin = PluginLibrary::load(&path)?;
+        if BUILTIN_MODULES.contains(&plugin.name()) || plugins.iter().any(|p| p.name() == plugin.name()) {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "plugin {} uses the module name {}, which is already taken",
+                path.display(),
+                plugin.name()
+            )));
+        }
+        info!("[{}] Loaded plugin {} from {}", make_instance_id(), plugin.name(), path.display());
+        plugins.push(plugin);
+    }
+    Ok(plugins)
+}
+
+/// Module instance created by a plugin
+pub struct PluginModule {
+    plugin: PluginLibrary,
+    instance: *mut c_void,
+}
+impl ByteProcessor for PluginModule {
+    fn name(&self) -> &'static str { self.plugin.name }
+    fn process(&self, input: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut output = PluginBuffer::empty();
+        let status = unsafe {
+            ((*self.plugin.descriptor).process)(self.instance, input.as_ptr(), input.len(), &mut output)
+        };
+        let data = self.plugin.take_buffer(output);
+        if status != 0 {
+            return Err(ByteProcError::Module(format!(
+                "plugin {} failed ({}): {}",
+                self.plugin.name,
+                status,
+                String::from_utf8_lossy(&data)
+            )));
+        }
+        Ok(data)
+    }
+}
+impl Drop for PluginModule {
+    fn drop(&mut self) {
+        unsafe { ((*self.plugin.descriptor).destroy)(self.instance) };
+    }
+}
+
+// -------------- Config structures --------------
+
+/// Subcommands; without one byteproc processes messages
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Receive log records from other instances on `--log-zmq-socket` and
+    /// write them merged
+    LogCollector(LogCollectorArgs),
+}
+
+/// Options of the `log-collector` subcommand
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct LogCollectorArgs {
+    /// Append merged records to this file instead of stdout
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+
+    /// Write merged records as JSON lines instead of text
+    #[arg(long)]
+    pub json: bool,
+
+    /// Hold records this long to order them by timestamp
+    #[arg(long, default_value_t = 1000)]
+    pub window_ms: u64,
+
+    /// Stop after this long without records; 0 runs until killed
+    #[arg(long, default_value_t = 0)]
+    pub idle_ms: u64,
+}
+
+impl Default for LogCollectorArgs {
+    fn default() -> Self {
+        LogCollectorArgs { file: None, json: false, window_ms: 1000, idle_ms: 0 }
+    }
+}
+
+#[derive(Parser, Serialize, Deserialize, Debug, Clone)]
+#[command(name = "byteproc")]
+pub struct Config {
+    /// Path to config file
+    #[arg(long)]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// Run something other than message processing (command line only)
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+
+    /// Maximum stream size in KB
+    #[arg(long, default_value_t = 64)]
+    #[serde(default = "default_max_stream_size_kb")]
+    pub max_stream_size_kb: usize,
+
+    /// Stream stdin to stdout in chunks instead of reading one whole message
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Chunk size in KB for --stream
+    #[arg(long, default_value_t = 64)]
+    #[serde(default = "default_stream_chunk_kb")]
+    pub stream_chunk_kb: usize,
+
+    // Input/Output options
+    #[arg(long, default_value = "stdin")]
+    #[serde(default)]
+    pub input_type: InputType,
+
+    #[arg(long)]
+    #[serde(default)]
+    pub input_zmq_socket: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub input_zmq_bind: bool,
+
+    #[arg(long, default_value = "stdout")]
+    #[serde(default)]
+    pub output_type: OutputType,
+
+    #[arg(long)]
+    #[serde(default)]
+    pub output_zmq_socket: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub output_zmq_bind: bool,
+
+    /// File for --output-type file
+    #[arg(long)]
+    #[serde(default)]
+    pub output_file: Option<PathBuf>,
+
+    /// Outputs that all receive every message (config file only).
+    /// When set, it replaces output_type and its options.
+    #[arg(skip)]
+    #[serde(default)]
+    pub outputs: Vec<OutputSpec>,
+
+    /// PULL endpoints merged into one processing loop (config file only).
+    /// When set, it replaces input_type and its options.
+    #[arg(skip)]
+    #[serde(default)]
+    pub inputs: Vec<InputSpec>,
+
+    /// Messages to process from ZMQ before exiting, 0 = until no message
+    /// arrives within zmq_receive_timeout_ms
+    #[arg(long, default_value_t = 1)]
+    #[serde(default = "default_message_count")]
+    pub message_count: u64,
+
+    /// How a failed run reports on stderr: text or json
+    #[arg(long, default_value = "text")]
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+
+    /// What to do with a message that fails: abort, skip or dead_letter
+    #[arg(long, default_value = "abort")]
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+
+    /// File that dead letters are appended to, one JSON object per line
+    #[arg(long)]
+    #[serde(default)]
+    pub dead_letter_file: Option<PathBuf>,
+
+    /// ZMQ endpoint that dead letters are pushed to
+    #[arg(long)]
+    #[serde(default)]
+    pub dead_letter_zmq_socket: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub dead_letter_zmq_bind: bool,
+
+    // ZMQ options
+    #[arg(long, default_value_t = 1000)]
+    #[serde(default = "default_zmq_reconnect_interval_ms")]
+    pub zmq_reconnect_interval_ms: u32,
+
+    #[arg(long, default_value_t = 5)]
+    #[serde(default = "default_zmq_max_reconnect_attempts")]
+    pub zmq_max_reconnect_attempts: u32,
+
+    #[arg(long, default_value_t = 5000)]
+    #[serde(default = "default_zmq_send_timeout_ms")]
+    pub zmq_send_timeout_ms: i32,
+
+    #[arg(long, default_value_t = 5000)]
+    #[serde(default = "default_zmq_receive_timeout_ms")]
+    pub zmq_receive_timeout_ms: i32,
+
+    #[arg(long, default_value_t = 0)]
+    #[serde(default = "default_zmq_linger_ms")]
+    pub zmq_linger_ms: i32,
+
+    /// Send the message context as a second ZMQ frame
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub zmq_send_context: bool,
+
+    // Logging options
+    #[arg(long, default_value_t = true)]
+    #[serde(default = "default_log_enabled")]
+    pub log_enabled: bool,
+
+    #[arg(long, default_value = "info")]
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+
+    #[arg(long, default_value = "byteproc.log")]
+    #[serde(default = "default_log_file")]
+    pub log_file: String,
+
+    #[arg(long, default_value_t = true)]
+    #[serde(default = "default_log_append")]
+    pub log_append: bool,
+
+    /// Write JSON-lines log records instead of text
+    #[arg(long)]
+    #[serde(default)]
+    pub log_structured: bool,
+
+    /// Rotate the log file when it would grow past this size; 0 disables rotation
+    #[arg(long, default_value_t = 0)]
+    #[serde(default)]
+    pub log_max_file_size_mb: u64,
+
+    /// Number of rotated files to keep (`<log_file>.1` is the newest)
+    #[arg(long, default_value_t = 5)]
+    #[serde(default = "default_log_rotation_count")]
+    pub log_rotation_count: u32,
+
+    /// Also publish log records to this ZMQ endpoint; the log collector binds it
+    #[arg(long)]
+    #[serde(default)]
+    pub log_zmq_socket: Option<String>,
+
+    #[arg(long, default_value = "push")]
+    #[serde(default)]
+    pub log_zmq_type: LogZmqType,
+
+    // Processing modules
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub xor_enabled: bool,
+
+    #[arg(long)]
+    #[serde(default)]
+    pub xor_key: Option<String>,
+
+    #[arg(long, default_value = "00")]
+    #[serde(default = "default_xor_pad")]
+    pub xor_pad: String,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub base64_enabled: bool,
+
+    #[arg(long, default_value = "encode")]
+    #[serde(default = "default_base64_mode")]
+    pub base64_mode: Base64Mode,
+
+    #[arg(long, default_value_t = true)]
+    #[serde(default = "default_base64_padding")]
+    pub base64_padding: bool,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub linecode_enabled: bool,
+
+    #[arg(long, default_value = "manchester_ieee")]
+    #[serde(default = "default_linecode_scheme")]
+    pub linecode_scheme: LineCodeScheme,
+
+    #[arg(long, default_value = "encode")]
+    #[serde(default = "default_linecode_mode")]
+    pub linecode_mode: LineCodeMode,
+
+    /// Line-coded side uses one bit per byte (GNU Radio style)
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub linecode_unpacked: bool,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub bits_enabled: bool,
+
+    #[arg(long, default_value = "unpack")]
+    #[serde(default = "default_bits_mode")]
+    pub bits_mode: BitsMode,
+
+    /// Bits carried in each unpacked byte (1-8)
+    #[arg(long, default_value_t = 1)]
+    #[serde(default = "default_bits_per_byte")]
+    pub bits_per_byte: u8,
+
+    #[arg(long, default_value = "msb")]
+    #[serde(default = "default_bits_order")]
+    pub bits_order: BitOrder,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub framer_enabled: bool,
+
+    /// Hex bytes sent before the sync word
+    #[arg(long, default_value = "aaaaaaaa")]
+    #[serde(default = "default_framer_preamble")]
+    pub framer_preamble: String,
+
+    /// Hex sync word (shared with the deframer)
+    #[arg(long, default_value = "1acffc1d")]
+    #[serde(default = "default_framer_sync_word")]
+    pub framer_sync_word: String,
+
+    /// Width of the length field in bytes (1, 2 or 4)
+    #[arg(long, default_value_t = 2)]
+    #[serde(default = "default_framer_length_bytes")]
+    pub framer_length_bytes: u8,
+
+    #[arg(long, default_value = "big")]
+    #[serde(default = "default_framer_length_endian")]
+    pub framer_length_endian: Endianness,
+
+    #[arg(long, default_value = "none")]
+    #[serde(default = "default_framer_crc")]
+    pub framer_crc: FrameCrc,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub deframer_enabled: bool,
+
+    /// Bit errors tolerated when matching the sync word
+    #[arg(long, default_value_t = 0)]
+    #[serde(default)]
+    pub deframer_max_bit_errors: u32,
+
+    #[arg(long, default_value = "byte")]
+    #[serde(default = "default_deframer_search")]
+    pub deframer_search: FrameSearch,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub channel_enabled: bool,
+
+    #[arg(long, default_value = "bsc")]
+    #[serde(default = "default_channel_model")]
+    pub channel_model: ChannelModel,
+
+    /// RNG seed, the same seed reproduces the same errors
+    #[arg(long, default_value_t = 1)]
+    #[serde(default = "default_channel_seed")]
+    pub channel_seed: u64,
+
+    /// Bit error rate (good state BER for gilbert_elliott)
+    #[arg(long, default_value_t = 0.0)]
+    #[serde(default)]
+    pub channel_ber: f64,
+
+    #[arg(long, default_value_t = 0.0)]
+    #[serde(default)]
+    pub channel_ge_p_good_to_bad: f64,
+
+    #[arg(long, default_value_t = 0.1)]
+    #[serde(default = "default_channel_ge_p_bad_to_good")]
+    pub channel_ge_p_bad_to_good: f64,
+
+    #[arg(long, default_value_t = 0.5)]
+    #[serde(default = "default_channel_ge_ber_bad")]
+    pub channel_ge_ber_bad: f64,
+
+    /// Per-byte erasure probability
+    #[arg(long, default_value_t = 0.0)]
+    #[serde(default)]
+    pub channel_erasure_rate: f64,
+
+    /// Hex byte written in place of an erased byte
+    #[arg(long, default_value = "00")]
+    #[serde(default = "default_channel_erasure_byte")]
+    pub channel_erasure_byte: String,
+
+    /// Bytes dropped from the end of each message
+    #[arg(long, default_value_t = 0)]
+    #[serde(default)]
+    pub channel_truncate_bytes: usize,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub slice_enabled: bool,
+
+    #[arg(long, default_value_t = 0)]
+    #[serde(default)]
+    pub slice_offset: usize,
+
+    /// Bytes to keep after the offset (default: to the end)
+    #[arg(long)]
+    #[serde(default)]
+    pub slice_length: Option<usize>,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub pad_enabled: bool,
+
+    #[arg(long, default_value = "pad")]
+    #[serde(default = "default_pad_mode")]
+    pub pad_mode: PadMode,
+
+    #[arg(long, default_value = "pkcs7")]
+    #[serde(default = "default_pad_scheme")]
+    pub pad_scheme: PadScheme,
+
+    #[arg(long, default_value_t = 16)]
+    #[serde(default = "default_pad_block_size")]
+    pub pad_block_size: usize,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub prepend_enabled: bool,
+
+    /// Hex header added before the data
+    #[arg(long, default_value = "")]
+    #[serde(default)]
+    pub prepend_hex: String,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub append_enabled: bool,
+
+    /// Hex trailer added after the data
+    #[arg(long, default_value = "")]
+    #[serde(default)]
+    pub append_hex: String,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub reverse_enabled: bool,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub swap_enabled: bool,
+
+    /// Word size for endianness swapping (16, 32 or 64)
+    #[arg(long, default_value_t = 16)]
+    #[serde(default = "default_swap_word_bits")]
+    pub swap_word_bits: u8,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub bitreverse_enabled: bool,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub bitops_enabled: bool,
+
+    /// `and`, `or`, `not`, `rotl`, `rotr`, `shl` or `shr`
+    #[arg(long, default_value = "not")]
+    #[serde(default = "default_bitops_op")]
+    pub bitops_op: BitOp,
+
+    /// Hex mask for `and`/`or`, cycled like the XOR key
+    #[arg(long)]
+    #[serde(default)]
+    pub bitops_mask: Option<String>,
+
+    /// Bit count for rotations and shifts
+    #[arg(long, default_value_t = 1)]
+    #[serde(default = "default_bitops_amount")]
+    pub bitops_amount: u32,
+
+    #[arg(long, default_value = "byte")]
+    #[serde(default = "default_bitops_scope")]
+    pub bitops_scope: BitScope,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub sbox_enabled: bool,
+
+    /// 256-entry substitution table as 512 hex characters
+    #[arg(long)]
+    #[serde(default)]
+    pub sbox_table: Option<String>,
+
+    /// File containing the substitution table as hex text
+    #[arg(long)]
+    #[serde(default)]
+    pub sbox_table_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "forward")]
+    #[serde(default = "default_sbox_mode")]
+    pub sbox_mode: SboxMode,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub rle_enabled: bool,
+
+    #[arg(long, default_value = "encode")]
+    #[serde(default = "default_rle_mode")]
+    pub rle_mode: RleMode,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub huffman_enabled: bool,
+
+    #[arg(long, default_value = "encode")]
+    #[serde(default = "default_huffman_mode")]
+    pub huffman_mode: HuffmanMode,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub repetition_enabled: bool,
+
+    #[arg(long, default_value = "encode")]
+    #[serde(default = "default_repetition_mode")]
+    pub repetition_mode: RepetitionMode,
+
+    /// Copies of each unit, must be odd
+    #[arg(long, default_value_t = 3)]
+    #[serde(default = "default_repetition_count")]
+    pub repetition_count: usize,
+
+    #[arg(long, default_value = "bit")]
+    #[serde(default = "default_repetition_unit")]
+    pub repetition_unit: RepetitionUnit,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub symbols_enabled: bool,
+
+    #[arg(long, default_value = "map")]
+    #[serde(default = "default_symbols_mode")]
+    pub symbols_mode: SymbolsMode,
+
+    /// Bits per symbol (1-8)
+    #[arg(long, default_value_t = 2)]
+    #[serde(default = "default_symbols_bits")]
+    pub symbols_bits: u8,
+
+    /// Gray code the symbol indices
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub symbols_gray: bool,
+
+    #[arg(long, default_value = "msb")]
+    #[serde(default = "default_symbols_order")]
+    pub symbols_order: BitOrder,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub script_enabled: bool,
+
+    /// Rhai script defining fn process(bytes)
+    #[arg(long)]
+    #[serde(default)]
+    pub script_file: Option<PathBuf>,
+
+    /// Operation budget per message
+    #[arg(long, default_value_t = 1_000_000)]
+    #[serde(default = "default_script_max_operations")]
+    pub script_max_operations: u64,
+
+    /// Per-object cap for scripts: KB per blob or string, 1024 elements
+    /// per KB for arrays and maps
+    #[arg(long, default_value_t = 1024)]
+    #[serde(default = "default_script_max_size_kb")]
+    pub script_max_size_kb: usize,
+
+    /// Wall-clock limit per message in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    #[serde(default = "default_script_timeout_ms")]
+    pub script_timeout_ms: u64,
+
+    #[arg(long, default_value_t = false)]
+    #[serde(default)]
+    pub wasm_enabled: bool,
+
+    /// WebAssembly module exporting memory, alloc and process
+    #[arg(long)]
+    #[serde(default)]
+    pub wasm_file: Option<PathBuf>,
+
+    /// Fuel per message, roughly one unit per instruction
+    #[arg(long, default_value_t = 10_000_000)]
+    #[serde(default = "default_wasm_fuel")]
+    pub wasm_fuel: u64,
+
+    /// Maximum linear memory in KB
+    #[arg(long, default_value_t = 16384)]
+    #[serde(default = "default_wasm_max_memory_kb")]
+    pub wasm_max_memory_kb: usize,
+
+    /// Ordered stages (built-in or plugin module names). When set, it
+    /// replaces the *_enabled flags and the fixed module order.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub pipeline: Vec<StageSpec>,
+
+    /// Reverse the pipeline and invert every module: emit the config or run it
+    #[arg(long, default_value = "off")]
+    #[serde(default)]
+    pub invert_pipeline: InvertPipeline,
+
+    /// Directory of plugin libraries
+    #[arg(long)]
+    #[serde(default)]
+    pub plugin_dir: Option<PathBuf>,
+
+    /// JSON config per plugin name (config file only)
+    #[arg(skip)]
+    #[serde(default)]
+    pub plugin_config: HashMap<String, serde_json::Value>,
+
+    /// Named stage lists that routes can send messages through (config file only)
+    #[arg(skip)]
+    #[serde(default)]
+    pub pipelines: HashMap<String, Vec<StageSpec>>,
+
+    /// Routing rules, first match wins (config file only)
+    #[arg(skip)]
+    #[serde(default)]
+    pub routes: Vec<RouteSpec>,
+}
+
+// Default function implementations
+fn default_max_stream_size_kb() -> usize { 64 }
+fn default_input_type() -> InputType { InputType::Stdin }
+fn default_output_type() -> OutputType { OutputType::Stdout }
+fn default_message_count() -> u64 { 1 }
+fn default_zmq_reconnect_interval_ms() -> u32 { 1000 }
+fn default_zmq_max_reconnect_attempts() -> u32 { 5 }
+fn default_zmq_send_timeout_ms() -> i32 { 5000 }
+fn default_zmq_receive_timeout_ms() -> i32 { 5000 }
+fn default_zmq_linger_ms() -> i32 { 3000 }
+fn default_log_enabled() -> bool { true }
+fn default_log_level() -> String { "info".into() }
+fn default_log_file() -> String { "byteproc.log".into() }
+fn default_log_append() -> bool { true }
+fn default_log_rotation_count() -> u32 { 5 }
+fn default_xor_pad() -> String { "00".into() }
+fn default_base64_mode() -> Base64Mode { Base64Mode::Encode }
+fn default_base64_padding() -> bool { true }
+fn default_linecode_scheme() -> LineCodeScheme { LineCodeScheme::ManchesterIeee }
+fn default_linecode_mode() -> LineCodeMode { LineCodeMode::Encode }
+fn default_bits_mode() -> BitsMode { BitsMode::Unpack }
+fn default_bits_per_byte() -> u8 { 1 }
+fn default_bits_order() -> BitOrder { BitOrder::Msb }
+fn default_framer_preamble() -> String { "aaaaaaaa".into() }
+fn default_framer_sync_word() -> String { "1acffc1d".into() }
+fn default_framer_length_bytes() -> u8 { 2 }
+fn default_framer_length_endian() -> Endianness { Endianness::Big }
+fn default_framer_crc() -> FrameCrc { FrameCrc::None }
+fn default_deframer_search() -> FrameSearch { FrameSearch::Byte }
+fn default_channel_model() -> ChannelModel { ChannelModel::Bsc }
+fn default_channel_seed() -> u64 { 1 }
+fn default_channel_ge_p_bad_to_good() -> f64 { 0.1 }
+fn default_channel_ge_ber_bad() -> f64 { 0.5 }
+fn default_channel_erasure_byte() -> String { "00".into() }
+fn default_pad_mode() -> PadMode { PadMode::Pad }
+fn default_pad_scheme() -> PadScheme { PadScheme::Pkcs7 }
+fn default_pad_block_size() -> usize { 16 }
+fn default_swap_word_bits() -> u8 { 16 }
+fn default_bitops_op() -> BitOp { BitOp::Not }
+fn default_bitops_amount() -> u32 { 1 }
+fn default_bitops_scope() -> BitScope { BitScope::Byte }
+fn default_sbox_mode() -> SboxMode { SboxMode::Forward }
+fn default_rle_mode() -> RleMode { RleMode::Encode }
+fn default_huffman_mode() -> HuffmanMode { HuffmanMode::Encode }
+fn default_repetition_mode() -> RepetitionMode { RepetitionMode::Encode }
+fn default_repetition_count() -> usize { 3 }
+fn default_repetition_unit() -> RepetitionUnit { RepetitionUnit::Bit }
+fn default_symbols_mode() -> SymbolsMode { SymbolsMode::Map }
+fn default_symbols_bits() -> u8 { 2 }
+fn default_symbols_order() -> BitOrder { BitOrder::Msb }
+fn default_script_max_operations() -> u64 { 1_000_000 }
+fn default_script_max_size_kb() -> usize { 1024 }
+fn default_script_timeout_ms() -> u64 { 1000 }
+fn default_wasm_fuel() -> u64 { 10_000_000 }
+fn default_stream_chunk_kb() -> usize { 64 }
+fn default_wasm_max_memory_kb() -> usize { 16384 }
+
+// Implement the Default trait for Config
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            config: None,
+            command: None,
+            max_stream_size_kb: default_max_stream_size_kb(),
+            stream: false, // Default for bool
+            stream_chunk_kb: default_stream_chunk_kb(),
+            input_type: default_input_type(),
+            input_zmq_socket: None,
+            input_zmq_bind: false, // Default for bool
+            output_type: default_output_type(),
+            output_zmq_socket: None,
+            output_zmq_bind: false, // Default for bool
+            output_file: None,
+            outputs: Vec::new(),
+            inputs: Vec::new(),
+            message_count: default_message_count(),
+            error_format: ErrorFormat::Text,
+            error_policy: ErrorPolicy::Abort,
+            dead_letter_file: None,
+            dead_letter_zmq_socket: None,
+            dead_letter_zmq_bind: false, // Default for bool
+            zmq_reconnect_interval_ms: default_zmq_reconnect_interval_ms(),
+            zmq_max_reconnect_attempts: default_zmq_max_reconnect_attempts(),
+            zmq_send_timeout_ms: default_zmq_send_timeout_ms(),
+            zmq_receive_timeout_ms: default_zmq_receive_timeout_ms(),
+            zmq_linger_ms: default_zmq_linger_ms(),
+            zmq_send_context: false, // Default for bool
+            log_enabled: default_log_enabled(),
+            log_level: default_log_level(),
+            log_file: default_log_file(),
+            log_append: default_log_append(),
+            log_structured: false,
+            log_max_file_size_mb: 0,
+            log_rotation_count: default_log_rotation_count(),
+            log_zmq_socket: None,
+            log_zmq_type: LogZmqType::Push,
+            xor_enabled: false, // Default for bool
+            xor_key: None,
+            xor_pad: default_xor_pad(),
+            base64_enabled: false, // Default for bool
+            base64_mode: default_base64_mode(),
+            base64_padding: default_base64_padding(),
+            linecode_enabled: false, // Default for bool
+            linecode_scheme: default_linecode_scheme(),
+            linecode_mode: default_linecode_mode(),
+            linecode_unpacked: false, // Default for bool
+            bits_enabled: false, // Default for bool
+            bits_mode: default_bits_mode(),
+            bits_per_byte: default_bits_per_byte(),
+            bits_order: default_bits_order(),
+            framer_enabled: false, // Default for bool
+            framer_preamble: default_framer_preamble(),
+            framer_sync_word: default_framer_sync_word(),
+            framer_length_bytes: default_framer_length_bytes(),
+            framer_length_endian: default_framer_length_endian(),
+            framer_crc: default_framer_crc(),
+            deframer_enabled: false, // Default for bool
+            deframer_max_bit_errors: 0,
+            deframer_search: default_deframer_search(),
+            channel_enabled: false, // Default for bool
+            channel_model: default_channel_model(),
+            channel_seed: default_channel_seed(),
+            channel_ber: 0.0,
+            channel_ge_p_good_to_bad: 0.0,
+            channel_ge_p_bad_to_good: default_channel_ge_p_bad_to_good(),
+            channel_ge_ber_bad: default_channel_ge_ber_bad(),
+            channel_erasure_rate: 0.0,
+            channel_erasure_byte: default_channel_erasure_byte(),
+            channel_truncate_bytes: 0,
+            slice_enabled: false, // Default for bool
+            slice_offset: 0,
+            slice_length: None,
+            pad_enabled: false, // Default for bool
+            pad_mode: default_pad_mode(),
+            pad_scheme: default_pad_scheme(),
+            pad_block_size: default_pad_block_size(),
+            prepend_enabled: false, // Default for bool
+            prepend_hex: String::new(),
+            append_enabled: false, // Default for bool
+            append_hex: String::new(),
+            reverse_enabled: false, // Default for bool
+            swap_enabled: false, // Default for bool
+            swap_word_bits: default_swap_word_bits(),
+            bitreverse_enabled: false, // Default for bool
+            bitops_enabled: false, // Default for bool
+            bitops_op: default_bitops_op(),
+            bitops_mask: None,
+            bitops_amount: default_bitops_amount(),
+            bitops_scope: default_bitops_scope(),
+            sbox_enabled: false, // Default for bool
+            sbox_table: None,
+            sbox_table_file: None,
+            sbox_mode: default_sbox_mode(),
+            rle_enabled: false, // Default for bool
+            rle_mode: default_rle_mode(),
+            huffman_enabled: false, // Default for bool
+            huffman_mode: default_huffman_mode(),
+            repetition_enabled: false, // Default for bool
+            repetition_mode: default_repetition_mode(),
+            repetition_count: default_repetition_count(),
+            repetition_unit: default_repetition_unit(),
+            symbols_enabled: false, // Default for bool
+            symbols_mode: default_symbols_mode(),
+            symbols_bits: default_symbols_bits(),
+            symbols_gray: false, // Default for bool
+            symbols_order: default_symbols_order(),
+            script_enabled: false, // Default for bool
+            script_file: None,
+            script_max_operations: default_script_max_operations(),
+            script_max_size_kb: default_script_max_size_kb(),
+            script_timeout_ms: default_script_timeout_ms(),
+            wasm_enabled: false, // Default for bool
+            wasm_file: None,
+            wasm_fuel: default_wasm_fuel(),
+            wasm_max_memory_kb: default_wasm_max_memory_kb(),
+            pipeline: Vec::new(),
+            invert_pipeline: InvertPipeline::Off,
+            plugin_dir: None,
+            plugin_config: HashMap::new(),
+            pipelines: HashMap::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Calculated field: Maximum stream size in bytes
+    pub fn max_stream_size(&self) -> Result<usize, ByteProcError> {
+        self.max_stream_size_kb
+            .checked_mul(1024)
+            .ok_or_else(|| ByteProcError::InvalidConfiguration("max_stream_size_kb too large".into()))
+    }
+    
+    /// Calculated field: log rotation size in bytes, 0 when rotation is off
+    pub fn log_max_file_size(&self) -> Result<u64, ByteProcError> {
+        self.log_max_file_size_mb
+            .checked_mul(1024 * 1024)
+            .ok_or_else(|| ByteProcError::InvalidConfiguration("log_max_file_size_mb too large".into()))
+    }
+
+    /// Calculated field: outputs for this run, falling back to output_type
+    pub fn output_specs(&self) -> Vec<OutputSpec> {
+        if !self.outputs.is_empty() {
+            return self.outputs.clone();
+        }
+        vec![OutputSpec {
+            name: None,
+            output_type: self.output_type.clone(),
+            endpoint: self.output_zmq_socket.clone(),
+            bind: self.output_zmq_bind,
+            path: self.output_file.clone(),
+            on_error: SinkErrorPolicy::Fail,
+        }]
+    }
+
+    /// Calculated field: ZMQ inputs for this run, empty when reading stdin
+    pub fn input_specs(&self) -> Vec<InputSpec> {
+        if !self.inputs.is_empty() {
+            return self.inputs.clone();
+        }
+        match (&self.input_type, &self.input_zmq_socket) {
+            (InputType::ZmqPull, Some(endpoint)) => vec![InputSpec {
+                endpoint: endpoint.clone(),
+                bind: self.input_zmq_bind,
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Calculated field: Base64 encode mode
+    pub fn base64_encode(&self) -> bool {
+        self.base64_mode == Base64Mode::Encode
+    }
+    
+    /// Calculated field: Line coding encode mode
+    pub fn linecode_encode(&self) -> bool {
+        self.linecode_mode == LineCodeMode::Encode
+    }
+
+    /// Calculated field: packet layout used by the framer and deframer
+    pub fn frame_format(&self) -> Result<FrameFormat, ByteProcError> {
+        FrameFormat::new(
+            &self.framer_preamble,
+            &self.framer_sync_word,
+            self.framer_length_bytes,
+            self.framer_length_endian,
+            self.framer_crc,
+        )
+    }
+
+    /// Calculated field: channel simulation settings
+    pub fn channel_params(&self) -> Result<ChannelParams, ByteProcError> {
+        let erasure_byte = u8::from_str_radix(&self.channel_erasure_byte, 16).map_err(|_| {
+            ByteProcError::InvalidConfiguration(format!(
+                "channel_erasure_byte must be a single hex byte, got {}",
+                self.channel_erasure_byte
+            ))
+        })?;
+        Ok(ChannelParams {
+            model: self.channel_model,
+            seed: self.channel_seed,
+            ber: self.channel_ber,
+            p_good_to_bad: self.channel_ge_p_good_to_bad,
+            p_bad_to_good: self.channel_ge_p_bad_to_good,
+            ber_bad: self.channel_ge_ber_bad,
+            erasure_rate: self.channel_erasure_rate,
+            erasure_byte,
+            truncate_bytes: self.channel_truncate_bytes,
+        })
+    }
+
+    /// Calculated field: substitution box from `sbox_table` or `sbox_table_file`
+    pub fn sbox(&self) -> Result<SboxModule, ByteProcError> {
+        let inverse = self.sbox_mode == SboxMode::Inverse;
+        match (&self.sbox_table, &self.sbox_table_file) {
+            (Some(table), None) => SboxModule::from_hex(table, inverse),
+            (None, Some(path)) => SboxModule::from_file(path, inverse),
+            _ => Err(ByteProcError::InvalidConfiguration(
+                "exactly one of sbox_table and sbox_table_file must be set".into(),
+            )),
+        }
+    }
+
+    /// Calculated field: compiled script from `script_file`
+    pub fn script(&self) -> Result<ScriptModule, ByteProcError> {
+        let path = self.script_file.as_ref().ok_or_else(|| {
+            ByteProcError::InvalidConfiguration("script_file must be set if script_enabled".into())
+        })?;
+        ScriptModule::from_file(
+            path,
+            ScriptLimits {
+                max_operations: self.script_max_operations,
+                max_size: self.script_max_size_kb * 1024,
+                timeout: Duration::from_millis(self.script_timeout_ms),
+            },
+        )
+    }
+
+    /// Calculated field: compiled WebAssembly module from `wasm_file`
+    pub fn wasm(&self) -> Result<WasmModule, ByteProcError> {
+        let path = self.wasm_file.as_ref().ok_or_else(|| {
+            ByteProcError::InvalidConfiguration("wasm_file must be set if wasm is enabled".into())
+        })?;
+        WasmModule::from_file(
+            path,
+            WasmLimits { fuel: self.wasm_fuel, max_memory: self.wasm_max_memory_kb * 1024 },
+        )
+    }
+
+    /// Calculated field: whether a built-in module runs, either because it
+    /// is listed in `pipeline` or, without a pipeline, its flag is set
+    pub fn stage_enabled(&self, name: &str) -> bool {
+        if !self.pipeline.is_empty() {
+            return self.pipeline.iter().any(|stage| stage.module == name);
+        }
+        match name {
+            MODULE_XOR => self.xor_enabled,
+            MODULE_BASE64 => self.base64_enabled,
+            MODULE_LINECODE => self.linecode_enabled,
+            MODULE_BITS => self.bits_enabled,
+            MODULE_FRAMER => self.framer_enabled,
+            MODULE_DEFRAMER => self.deframer_enabled,
+            MODULE_CHANNEL => self.channel_enabled,
+            MODULE_SLICE => self.slice_enabled,
+            MODULE_REVERSE => self.reverse_enabled,
+            MODULE_SWAP => self.swap_enabled,
+            MODULE_BITREVERSE => self.bitreverse_enabled,
+            MODULE_PAD => self.pad_enabled,
+            MODULE_PREPEND => self.prepend_enabled,
+            MODULE_APPEND => self.append_enabled,
+            MODULE_BITOPS => self.bitops_enabled,
+            MODULE_SBOX => self.sbox_enabled,
+            MODULE_RLE => self.rle_enabled,
+            MODULE_HUFFMAN => self.huffman_enabled,
+            MODULE_REPETITION => self.repetition_enabled,
+            MODULE_SYMBOLS => self.symbols_enabled,
+            MODULE_SCRIPT => self.script_enabled,
+            MODULE_WASM => self.wasm_enabled,
+            _ => false,
+        }
+    }
+
+    /// Calculated field: config that undoes this one. Stages run in reverse
+    /// order with their modes flipped; framer and deframer swap places.
+    pub fn inverted(&self) -> Result<Config, ByteProcError> {
+        if !self.routes.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "a config with routes can't be inverted".into(),
+            ));
+        }
+        let mut inv = self.clone();
+        inv.invert_pipeline = InvertPipeline::Off;
+        // Each stage describes its inverse through `Invertible`, the same
+        // code that `ModuleRegistry::inverse` runs
+        let mut pipeline = Vec::new();
+        for stage in self.stages().iter().rev() {
+            let no_inverse = || ByteProcError::InvalidConfiguration(format!("module {} has no inverse", stage.module));
+            if !BUILTIN_MODULES.contains(&stage.module.as_str()) {
+                return Err(no_inverse());
+            }
+            let module = create_module(&stage.module, self)?;
+            let invertible = module.as_invertible().ok_or_else(no_inverse)?;
+            let module = invertible.invert_config(&mut inv)?.to_string();
+            pipeline.push(StageSpec { module, on_error: stage.on_error });
+        }
+        inv.pipeline = pipeline;
+        Ok(inv)
+    }
+
+    /// Calculated field: stages in run order
+    pub fn stages(&self) -> Vec<StageSpec> {
+        if !self.pipeline.is_empty() {
+            return self.pipeline.clone();
+        }
+        BUILTIN_MODULES
+            .iter()
+            .filter(|name| self.stage_enabled(name))
+            .map(|&name| name.into())
+            .collect()
+    }
+
+    /// Calculated field: module names in run order
+    pub fn stage_names(&self) -> Vec<String> {
+        self.stages().into_iter().map(|stage| stage.module).collect()
+    }
+
+    /// Load the plugin libraries in `plugin_dir`, if set
+    pub fn plugins(&self) -> Result<Vec<PluginLibrary>, ByteProcError> {
+        match &self.plugin_dir {
+            Some(dir) => load_plugins(dir),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Check that every stage in `pipeline` and `pipelines` is a built-in
+    /// module or one of `plugins`
+    pub fn validate_stages(&self, plugins: &[PluginLibrary]) -> Result<(), ByteProcError> {
+        let stages = self.pipeline.iter().map(|s| ("pipeline", s))
+            .chain(self.pipelines.iter().flat_map(|(name, stages)| stages.iter().map(move |s| (name.as_str(), s))));
+        for (list, stage) in stages {
+            if !BUILTIN_MODULES.contains(&stage.module.as_str()) && !plugins.iter().any(|p| p.name() == stage.module) {
+                return Err(ByteProcError::InvalidConfiguration(format!(
+                    "{}: unknown module {}{}",
+                    list,
+                    stage.module,
+                    if self.plugin_dir.is_some() { "" } else { " (no plugin_dir set)" }
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Calculated field: XOR pad byte
+    pub fn xor_pad_byte(&self) -> Option<u8> {
+        u8::from_str_radix(&self.xor_pad, 16).ok()
+    }
+    
+    /// Load configuration from command line and optional config file
+    pub fn load() -> Result<Self, ByteProcError> {
+        // Parse command line args first
+        let cli_args = Self::parse(); // Renamed to cli_args to avoid confusion with config variable
+        
+        // Determine if we should load from a file
+        let mut config_from_file = if let Some(path) = &cli_args.config {
+            // Explicitly provided config file
+            Self::from_file(path)?
+        } else if let Ok(env_path) = std::env::var("BYTEPROC_CONFIG") {
+            // Config file from environment variable
+            Self::from_file(&PathBuf::from(env_path))?
+        } else if PathBuf::from("byteproc.json").exists() {
+            // Default config file in current directory
+            Self::from_file(&PathBuf::from("byteproc.json"))?
+        } else {
+            // No config file, use defaults
+            Self::default()
+        };
+        
+        // Override file/default values with CLI values where provided
+        // We check if the CLI arg was actually passed by clap or if it's using its own default.
+        // This requires checking against the default values defined by clap for simple types,
+        // or checking if Option types are Some.
+
+        // Create a default instance of CLI args to compare against
+        let default_cli_args = Self::try_parse_from(&["byteproc"]).unwrap_or_else(|_| Self::default());
+
+
+        if cli_args.max_stream_size_kb != default_cli_args.max_stream_size_kb {
+            config_from_file.max_stream_size_kb = cli_args.max_stream_size_kb;
+        }
+        config_from_file.command = cli_args.command;
+        if cli_args.stream != default_cli_args.stream {
+            config_from_file.stream = cli_args.stream;
+        }
+        if cli_args.stream_chunk_kb != default_cli_args.stream_chunk_kb {
+            config_from_file.stream_chunk_kb = cli_args.stream_chunk_kb;
+        }
+        if cli_args.input_type != default_cli_args.input_type {
+            config_from_file.input_type = cli_args.input_type;
+        }
+        if cli_args.input_zmq_socket.is_some() { // For Option types, just check if Some
+            config_from_file.input_zmq_socket = cli_args.input_zmq_socket;
+        }
+        if cli_args.input_zmq_bind != default_cli_args.input_zmq_bind {
+             config_from_file.input_zmq_bind = cli_args.input_zmq_bind;
+        }
+        if cli_args.output_type != default_cli_args.output_type {
+            config_from_file.output_type = cli_args.output_type;
+        }
+        if cli_args.output_zmq_socket.is_some() {
+            config_from_file.output_zmq_socket = cli_args.output_zmq_socket;
+        }
+        if cli_args.output_zmq_bind != default_cli_args.output_zmq_bind {
+            config_from_file.output_zmq_bind = cli_args.output_zmq_bind;
+        }
+        if cli_args.output_file.is_some() {
+            config_from_file.output_file = cli_args.output_file;
+        }
+        if cli_args.message_count != default_cli_args.message_count {
+            config_from_file.message_count = cli_args.message_count;
+        }
+        if cli_args.error_format != default_cli_args.error_format {
+            config_from_file.error_format = cli_args.error_format;
+        }
+        if cli_args.error_policy != default_cli_args.error_policy {
+            config_from_file.error_policy = cli_args.error_policy;
+        }
+        if cli_args.dead_letter_file.is_some() {
+            config_from_file.dead_letter_file = cli_args.dead_letter_file;
+        }
+        if cli_args.dead_letter_zmq_socket.is_some() {
+            config_from_file.dead_letter_zmq_socket = cli_args.dead_letter_zmq_socket;
+        }
+        if cli_args.dead_letter_zmq_bind != default_cli_args.dead_letter_zmq_bind {
+            config_from_file.dead_letter_zmq_bind = cli_args.dead_letter_zmq_bind;
+        }
+        if cli_args.zmq_reconnect_interval_ms != default_cli_args.zmq_reconnect_interval_ms {
+            config_from_file.zmq_reconnect_interval_ms = cli_args.zmq_reconnect_interval_ms;
+        }
+        if cli_args.zmq_max_reconnect_attempts != default_cli_args.zmq_max_reconnect_attempts {
+            config_from_file.zmq_max_reconnect_attempts = cli_args.zmq_max_reconnect_attempts;
+        }
+        if cli_args.zmq_send_timeout_ms != default_cli_args.zmq_send_timeout_ms {
+            config_from_file.zmq_send_timeout_ms = cli_args.zmq_send_timeout_ms;
+        }
+        if cli_args.zmq_receive_timeout_ms != default_cli_args.zmq_receive_timeout_ms {
+            config_from_file.zmq_receive_timeout_ms = cli_args.zmq_receive_timeout_ms;
+        }
+        if cli_args.zmq_linger_ms != default_cli_args.zmq_linger_ms {
+            config_from_file.zmq_linger_ms = cli_args.zmq_linger_ms;
+        }
+        if cli_args.zmq_send_context != default_cli_args.zmq_send_context {
+            config_from_file.zmq_send_context = cli_args.zmq_send_context;
+        }
+        if cli_args.log_enabled != default_cli_args.log_enabled {
+            config_from_file.log_enabled = cli_args.log_enabled;
+        }
+        if cli_args.log_level != default_cli_args.log_level {
+            config_from_file.log_level = cli_args.log_level;
+        }
+        if cli_args.log_file != default_cli_args.log_file {
+            config_from_file.log_file = cli_args.log_file;
+        }
+        if cli_args.log_append != default_cli_args.log_append {
+            config_from_file.log_append = cli_args.log_append;
+        }
+        if cli_args.log_structured != default_cli_args.log_structured {
+            config_from_file.log_structured = cli_args.log_structured;
+        }
+        if cli_args.log_max_file_size_mb != default_cli_args.log_max_file_size_mb {
+            config_from_file.log_max_file_size_mb = cli_args.log_max_file_size_mb;
+        }
+        if cli_args.log_rotation_count != default_cli_args.log_rotation_count {
+            config_from_file.log_rotation_count = cli_args.log_rotation_count;
+        }
+        if cli_args.log_zmq_socket.is_some() {
+            config_from_file.log_zmq_socket = cli_args.log_zmq_socket;
+        }
+        if cli_args.log_zmq_type != default_cli_args.log_zmq_type {
+            config_from_file.log_zmq_type = cli_args.log_zmq_type;
+        }
+        if cli_args.xor_enabled != default_cli_args.xor_enabled {
+            config_from_file.xor_enabled = cli_args.xor_enabled;
+        }
+        if cli_args.xor_key.is_some() {
+            config_from_file.xor_key = cli_args.xor_key;
+        }
+        if cli_args.xor_pad != default_cli_args.xor_pad {
+            config_from_file.xor_pad = cli_args.xor_pad;
+        }
+        if cli_args.base64_enabled != default_cli_args.base64_enabled {
+            config_from_file.base64_enabled = cli_args.base64_enabled;
+        }
+        if cli_args.base64_mode != default_cli_args.base64_mode {
+            config_from_file.base64_mode = cli_args.base64_mode;
+        }
+        if cli_args.base64_padding != default_cli_args.base64_padding {
+            config_from_file.base64_padding = cli_args.base64_padding;
+        }
+        if cli_args.linecode_enabled != default_cli_args.linecode_enabled {
+            config_from_file.linecode_enabled = cli_args.linecode_enabled;
+        }
+        if cli_args.linecode_scheme != default_cli_args.linecode_scheme {
+            config_from_file.linecode_scheme = cli_args.linecode_scheme;
+        }
+        if cli_args.linecode_mode != default_cli_args.linecode_mode {
+            config_from_file.linecode_mode = cli_args.linecode_mode;
+        }
+        if cli_args.linecode_unpacked != default_cli_args.linecode_unpacked {
+            config_from_file.linecode_unpacked = cli_args.linecode_unpacked;
+        }
+        if cli_args.bits_enabled != default_cli_args.bits_enabled {
+            config_from_file.bits_enabled = cli_args.bits_enabled;
+        }
+        if cli_args.bits_mode != default_cli_args.bits_mode {
+            config_from_file.bits_mode = cli_args.bits_mode;
+        }
+        if cli_args.bits_per_byte != default_cli_args.bits_per_byte {
+            config_from_file.bits_per_byte = cli_args.bits_per_byte;
+        }
+        if cli_args.bits_order != default_cli_args.bits_order {
+            config_from_file.bits_order = cli_args.bits_order;
+        }
+        if cli_args.framer_enabled != default_cli_args.framer_enabled {
+            config_from_file.framer_enabled = cli_args.framer_enabled;
+        }
+        if cli_args.framer_preamble != default_cli_args.framer_preamble {
+            config_from_file.framer_preamble = cli_args.framer_preamble;
+        }
+        if cli_args.framer_sync_word != default_cli_args.framer_sync_word {
+            config_from_file.framer_sync_word = cli_args.framer_sync_word;
+        }
+        if cli_args.framer_length_bytes != default_cli_args.framer_length_bytes {
+            config_from_file.framer_length_bytes = cli_args.framer_length_bytes;
+        }
+        if cli_args.framer_length_endian != default_cli_args.framer_length_endian {
+            config_from_file.framer_length_endian = cli_args.framer_length_endian;
+        }
+        if cli_args.framer_crc != default_cli_args.framer_crc {
+            config_from_file.framer_crc = cli_args.framer_crc;
+        }
+        if cli_args.deframer_enabled != default_cli_args.deframer_enabled {
+            config_from_file.deframer_enabled = cli_args.deframer_enabled;
+        }
+        if cli_args.deframer_max_bit_errors != default_cli_args.deframer_max_bit_errors {
+            config_from_file.deframer_max_bit_errors = cli_args.deframer_max_bit_errors;
+        }
+        if cli_args.deframer_search != default_cli_args.deframer_search {
+            config_from_file.deframer_search = cli_args.deframer_search;
+        }
+        if cli_args.channel_enabled != default_cli_args.channel_enabled {
+            config_from_file.channel_enabled = cli_args.channel_enabled;
+        }
+        if cli_args.channel_model != default_cli_args.channel_model {
+            config_from_file.channel_model = cli_args.channel_model;
+        }
+        if cli_args.channel_seed != default_cli_args.channel_seed {
+            config_from_file.channel_seed = cli_args.channel_seed;
+        }
+        if cli_args.channel_ber != default_cli_args.channel_ber {
+            config_from_file.channel_ber = cli_args.channel_ber;
+        }
+        if cli_args.channel_ge_p_good_to_bad != default_cli_args.channel_ge_p_good_to_bad {
+            config_from_file.channel_ge_p_good_to_bad = cli_args.channel_ge_p_good_to_bad;
+        }
+        if cli_args.channel_ge_p_bad_to_good != default_cli_args.channel_ge_p_bad_to_good {
+            config_from_file.channel_ge_p_bad_to_good = cli_args.channel_ge_p_bad_to_good;
+        }
+        if cli_args.channel_ge_ber_bad != default_cli_args.channel_ge_ber_bad {
+            config_from_file.channel_ge_ber_bad = cli_args.channel_ge_ber_bad;
+        }
+        if cli_args.channel_erasure_rate != default_cli_args.channel_erasure_rate {
+            config_from_file.channel_erasure_rate = cli_args.channel_erasure_rate;
+        }
+        if cli_args.channel_erasure_byte != default_cli_args.channel_erasure_byte {
+            config_from_file.channel_erasure_byte = cli_args.channel_erasure_byte;
+        }
+        if cli_args.channel_truncate_bytes != default_cli_args.channel_truncate_bytes {
+            config_from_file.channel_truncate_bytes = cli_args.channel_truncate_bytes;
+        }
+        if cli_args.slice_enabled != default_cli_args.slice_enabled {
+            config_from_file.slice_enabled = cli_args.slice_enabled;
+        }
+        if cli_args.slice_offset != default_cli_args.slice_offset {
+            config_from_file.slice_offset = cli_args.slice_offset;
+        }
+        if cli_args.slice_length.is_some() {
+            config_from_file.slice_length = cli_args.slice_length;
+        }
+        if cli_args.pad_enabled != default_cli_args.pad_enabled {
+            config_from_file.pad_enabled = cli_args.pad_enabled;
+        }
+        if cli_args.pad_mode != default_cli_args.pad_mode {
+            config_from_file.pad_mode = cli_args.pad_mode;
+        }
+        if cli_args.pad_scheme != default_cli_args.pad_scheme {
+            config_from_file.pad_scheme = cli_args.pad_scheme;
+        }
+        if cli_args.pad_block_size != default_cli_args.pad_block_size {
+            config_from_file.pad_block_size = cli_args.pad_block_size;
+        }
+        if cli_args.prepend_enabled != default_cli_args.prepend_enabled {
+            config_from_file.prepend_enabled = cli_args.prepend_enabled;
+        }
+        if cli_args.prepend_hex != default_cli_args.prepend_hex {
+            config_from_file.prepend_hex = cli_args.prepend_hex;
+        }
+        if cli_args.append_enabled != default_cli_args.append_enabled {
+            config_from_file.append_enabled = cli_args.append_enabled;
+        }
+        if cli_args.append_hex != default_cli_args.append_hex {
+            config_from_file.append_hex = cli_args.append_hex;
+        }
+        if cli_args.reverse_enabled != default_cli_args.reverse_enabled {
+            config_from_file.reverse_enabled = cli_args.reverse_enabled;
+        }
+        if cli_args.swap_enabled != default_cli_args.swap_enabled {
+            config_from_file.swap_enabled = cli_args.swap_enabled;
+        }
+        if cli_args.swap_word_bits != default_cli_args.swap_word_bits {
+            config_from_file.swap_word_bits = cli_args.swap_word_bits;
+        }
+        if cli_args.bitreverse_enabled != default_cli_args.bitreverse_enabled {
+            config_from_file.bitreverse_enabled = cli_args.bitreverse_enabled;
+        }
+        if cli_args.bitops_enabled != default_cli_args.bitops_enabled {
+            config_from_file.bitops_enabled = cli_args.bitops_enabled;
+        }
+        if cli_args.bitops_op != default_cli_args.bitops_op {
+            config_from_file.bitops_op = cli_args.bitops_op;
+        }
+        if cli_args.bitops_mask.is_some() {
+            config_from_file.bitops_mask = cli_args.bitops_mask;
+        }
+        if cli_args.bitops_amount != default_cli_args.bitops_amount {
+            config_from_file.bitops_amount = cli_args.bitops_amount;
+        }
+        if cli_args.bitops_scope != default_cli_args.bitops_scope {
+            config_from_file.bitops_scope = cli_args.bitops_scope;
+        }
+        if cli_args.sbox_enabled != default_cli_args.sbox_enabled {
+            config_from_file.sbox_enabled = cli_args.sbox_enabled;
+        }
+        if cli_args.sbox_table.is_some() {
+            config_from_file.sbox_table = cli_args.sbox_table;
+        }
+        if cli_args.sbox_table_file.is_some() {
+            config_from_file.sbox_table_file = cli_args.sbox_table_file;
+        }
+        if cli_args.sbox_mode != default_cli_args.sbox_mode {
+            config_from_file.sbox_mode = cli_args.sbox_mode;
+        }
+        if cli_args.rle_enabled != default_cli_args.rle_enabled {
+            config_from_file.rle_enabled = cli_args.rle_enabled;
+        }
+        if cli_args.rle_mode != default_cli_args.rle_mode {
+            config_from_file.rle_mode = cli_args.rle_mode;
+        }
+        if cli_args.huffman_enabled != default_cli_args.huffman_enabled {
+            config_from_file.huffman_enabled = cli_args.huffman_enabled;
+        }
+        if cli_args.huffman_mode != default_cli_args.huffman_mode {
+            config_from_file.huffman_mode = cli_args.huffman_mode;
+        }
+        if cli_args.repetition_enabled != default_cli_args.repetition_enabled {
+            config_from_file.repetition_enabled = cli_args.repetition_enabled;
+        }
+        if cli_args.repetition_mode != default_cli_args.repetition_mode {
+            config_from_file.repetition_mode = cli_args.repetition_mode;
+        }
+        if cli_args.repetition_count != default_cli_args.repetition_count {
+            config_from_file.repetition_count = cli_args.repetition_count;
+        }
+        if cli_args.repetition_unit != default_cli_args.repetition_unit {
+            config_from_file.repetition_unit = cli_args.repetition_unit;
+        }
+        if cli_args.symbols_enabled != default_cli_args.symbols_enabled {
+            config_from_file.symbols_enabled = cli_args.symbols_enabled;
+        }
+        if cli_args.symbols_mode != default_cli_args.symbols_mode {
+            config_from_file.symbols_mode = cli_args.symbols_mode;
+        }
+        if cli_args.symbols_bits != default_cli_args.symbols_bits {
+            config_from_file.symbols_bits = cli_args.symbols_bits;
+        }
+        if cli_args.symbols_gray != default_cli_args.symbols_gray {
+            config_from_file.symbols_gray = cli_args.symbols_gray;
+        }
+        if cli_args.symbols_order != default_cli_args.symbols_order {
+            config_from_file.symbols_order = cli_args.symbols_order;
+        }
+        if cli_args.script_enabled != default_cli_args.script_enabled {
+            config_from_file.script_enabled = cli_args.script_enabled;
+        }
+        if cli_args.script_file.is_some() {
+            config_from_file.script_file = cli_args.script_file;
+        }
+        if cli_args.script_max_operations != default_cli_args.script_max_operations {
+            config_from_file.script_max_operations = cli_args.script_max_operations;
+        }
+        if cli_args.script_max_size_kb != default_cli_args.script_max_size_kb {
+            config_from_file.script_max_size_kb = cli_args.script_max_size_kb;
+        }
+        if cli_args.script_timeout_ms != default_cli_args.script_timeout_ms {
+            config_from_file.script_timeout_ms = cli_args.script_timeout_ms;
+        }
+        if cli_args.wasm_enabled != default_cli_args.wasm_enabled {
+            config_from_file.wasm_enabled = cli_args.wasm_enabled;
+        }
+        if cli_args.wasm_file.is_some() {
+            config_from_file.wasm_file = cli_args.wasm_file;
+        }
+        if cli_args.wasm_fuel != default_cli_args.wasm_fuel {
+            config_from_file.wasm_fuel = cli_args.wasm_fuel;
+        }
+        if cli_args.wasm_max_memory_kb != default_cli_args.wasm_max_memory_kb {
+            config_from_file.wasm_max_memory_kb = cli_args.wasm_max_memory_kb;
+        }
+        if !cli_args.pipeline.is_empty() {
+            config_from_file.pipeline = cli_args.pipeline;
+        }
+        if cli_args.plugin_dir.is_some() {
+            config_from_file.plugin_dir = cli_args.plugin_dir;
+        }
+        if cli_args.invert_pipeline != default_cli_args.invert_pipeline {
+            config_from_file.invert_pipeline = cli_args.invert_pipeline;
+        }
+
+        // The config path itself from CLI should always override
+        if cli_args.config.is_some() {
+            config_from_file.config = cli_args.config;
+        }
+        
+        // Validate the final configuration
+        config_from_file.validate()?;
+        
+        Ok(config_from_file)
+    }
+    
+    /// Load configuration from a file
+    fn from_file(path: &PathBuf) -> Result<Self, ByteProcError> {
+        let file = File::open(path)
+            .map_err(|e| ByteProcError::Io(format!("Failed to open config file: {}", e)))?;
+            
+        let mut reader = io::BufReader::new(file);
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)
+            .map_err(|e| ByteProcError::Io(format!("Failed to read config file: {}", e)))?;
+            
+        serde_json::from_str(&contents)
+            .map_err(|e| ByteProcError::Io(format!("Failed to parse config file: {}", e)))
+    }
+    
+    /// Validate the configuration
+    pub fn validate(&self) -> Result<(), ByteProcError> {
+        // Check required fields for specific input/output types
+        if self.input_type == InputType::ZmqPull && self.input_zmq_socket.is_none() && self.inputs.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "input_zmq_socket must be set for zmq_pull".into(),
+            ));
+        }
+        
+        if self.output_type == OutputType::ZmqPush && self.output_zmq_socket.is_none() && self.outputs.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "output_zmq_socket must be set for zmq_push".into(),
+            ));
+        }
+
+        if self.output_type == OutputType::File && self.output_file.is_none() && self.outputs.is_empty() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "output_file must be set for file output".into(),
+            ));
+        }
+
+        self.log_max_file_size()?;
+        if matches!(self.command, Some(Command::LogCollector(_))) && self.log_zmq_socket.is_none() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "log_zmq_socket must be set for log-collector".into(),
+            ));
+        }
+        if self.log_max_file_size_mb > 0 && self.log_rotation_count == 0 {
+            return Err(ByteProcError::InvalidConfiguration(
+                "log_rotation_count must be at least 1 when log_max_file_size_mb is set".into(),
+            ));
+        }
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            match output.output_type {
+                OutputType::ZmqPush if output.endpoint.is_none() => {
+                    return Err(ByteProcError::InvalidConfiguration(format!(
+                        "outputs[{}]: endpoint must be set for zmq_push", i
+                    )));
+                }
+                OutputType::File if output.path.is_none() => {
+                    return Err(ByteProcError::InvalidConfiguration(format!(
+                        "outputs[{}]: path must be set for file", i
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        let output_names: Vec<String> = self.outputs.iter().filter_map(|o| o.name.clone()).collect();
+        for (i, route) in self.routes.iter().enumerate() {
+            route.when.compile().map_err(|e| match e {
+                ByteProcError::InvalidConfiguration(msg) => {
+                    ByteProcError::InvalidConfiguration(format!("routes[{}]: {}", i, msg))
+                }
+                other => other,
+            })?;
+            if let Some(pipeline) = &route.pipeline
+                && !self.pipelines.contains_key(pipeline)
+            {
+                return Err(ByteProcError::InvalidConfiguration(format!(
+                    "routes[{}]: unknown pipeline {}", i, pipeline
+                )));
+            }
+            if let Some(output) = route.outputs.iter().find(|o| !output_names.contains(o)) {
+                return Err(ByteProcError::InvalidConfiguration(format!(
+                    "routes[{}]: unknown output {}", i, output
+                )));
+            }
+        }
+
+        if self.error_policy == ErrorPolicy::DeadLetter
+            && self.dead_letter_file.is_none()
+            && self.dead_letter_zmq_socket.is_none()
+        {
+            return Err(ByteProcError::InvalidConfiguration(
+                "dead_letter needs dead_letter_file or dead_letter_zmq_socket".into(),
+            ));
+        }
+
+        let has_dead_letter_target = self.dead_letter_file.is_some() || self.dead_letter_zmq_socket.is_some();
+        if has_dead_letter_target && self.error_policy != ErrorPolicy::DeadLetter {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "dead_letter_file and dead_letter_zmq_socket need error_policy dead_letter, not {}",
+                self.error_policy
+            )));
+        }
+
+        let has_on_error = self.pipeline.iter().chain(self.pipelines.values().flatten())
+            .any(|stage| stage.on_error != StageErrorPolicy::Fail);
+        if self.stream && has_on_error {
+            return Err(ByteProcError::InvalidConfiguration(
+                "stream does not support on_error".into(),
+            ));
+        }
+
+        // With plugin_dir the stages are checked once the plugins are loaded
+        if self.plugin_dir.is_none() {
+            self.validate_stages(&[])?;
+        }
+
+        if self.invert_pipeline != InvertPipeline::Off {
+            self.inverted()?;
+        }
+
+        let multiple_io = !self.inputs.is_empty() || !self.outputs.is_empty() || !self.routes.is_empty();
+        if self.stream && (multiple_io || self.input_type != InputType::Stdin || self.output_type != OutputType::Stdout) {
+            return Err(ByteProcError::InvalidConfiguration(
+                "stream needs stdin input and stdout output".into(),
+            ));
+        }
+
+        if self.stream && self.stream_chunk_kb == 0 {
+            return Err(ByteProcError::InvalidConfiguration(
+                "stream_chunk_kb must be at least 1".into(),
+            ));
+        }
+
+        if self.stage_enabled(MODULE_XOR) && self.xor_key.is_none() {
+            return Err(ByteProcError::InvalidConfiguration(
+                "xor_key must be set if xor is enabled".into(),
+            ));
+        }
+
+        if self.stage_enabled(MODULE_BITS) && !(1..=8).contains(&self.bits_per_byte) {
+            return Err(ByteProcError::InvalidConfiguration(
+                "bits_per_byte must be between 1 and 8".into(),
+            ));
+        }
+
+        if self.stage_enabled(MODULE_FRAMER) || self.stage_enabled(MODULE_DEFRAMER) {
+            self.frame_format()?;
+        }
+
+        if self.stage_enabled(MODULE_CHANNEL) {
+            ChannelModule::new(self.channel_params()?)?;
+        }
+
+        if self.stage_enabled(MODULE_PAD) {
+            PadModule::new(self.pad_mode == PadMode::Pad, self.pad_scheme, self.pad_block_size)?;
+        }
+
+        if self.stage_enabled(MODULE_PREPEND) {
+            PrependModule::new(&self.prepend_hex)?;
+        }
+
+        if self.stage_enabled(MODULE_APPEND) {
+            AppendModule::new(&self.append_hex)?;
+        }
+
+        if self.stage_enabled(MODULE_SWAP) {
+            SwapModule::new(self.swap_word_bits)?;
+        }
+
+        if self.stage_enabled(MODULE_BITOPS) {
+            BitopsModule::new(self.bitops_op, self.bitops_mask.as_deref(), self.bitops_amount, self.bitops_scope)?;
+        }
+
+        if self.stage_enabled(MODULE_SBOX) {
+            self.sbox()?;
+        }
+
+        if self.stage_enabled(MODULE_REPETITION) {
+            RepetitionModule::new(true, self.repetition_count, self.repetition_unit)?;
+        }
+
+        if self.stage_enabled(MODULE_SYMBOLS) {
+            SymbolsModule::new(true, self.symbols_bits, self.symbols_gray, self.symbols_order)?;
+        }
+
+        if self.stage_enabled(MODULE_SCRIPT) {
+            self.script()?;
+        }
+
+        if self.stage_enabled(MODULE_WASM) {
+            self.wasm()?;
+        }
+        
+        Ok(())
+    }
+}
+
+// -------------- Helpers --------------
+// Static instance ID initialized on first access
+static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+
+/// Generate a unique instance identifier for logging
+/// The ID is generated only once per process and then reused
+fn make_instance_id() -> &'static str {
+    INSTANCE_ID.get_or_init(|| {
+        format!("pid-{}-{:x}", 
+            std::process::id(), 
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() & 0xFFFF)
+    })
+}
+
+// First sequence number of this instance, see `message_sequence`
+static SEQUENCE_BASE: OnceLock<u64> = OnceLock::new();
+
+/// Sequence number for the `index`-th message entering the chain here.
+/// Numbering starts at the instance start time, so messages from separate
+/// runs, such as one-shot stdin runs, do not share sequence numbers.
+fn message_sequence(index: u64) -> u64 {
+    SEQUENCE_BASE.get_or_init(unix_time_us).wrapping_add(index)
+}
+
+/// Microseconds since the Unix epoch
+fn unix_time_us() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// Expand packed bytes into one bit (0 or 1) per byte
+fn bytes_to_bits(input: &[u8], order: BitOrder) -> Vec<u8> {
+    let mut bits = Vec::with_capacity(input.len() * 8);
+    for &byte in input {
+        for i in 0..8 {
+            bits.push((byte >> bit_shift(i, 8, order)) & 1);
+        }
+    }
+    bits
+}
+
+/// Pack one bit per byte back into bytes. A trailing partial byte is zero
+/// padded in the positions that were not filled.
+fn bits_to_bytes(bits: &[u8], order: BitOrder) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &b)| acc | ((b & 1) << bit_shift(i, 8, order)))
+        })
+        .collect()
+}
+
+/// Shift of the i-th transmitted bit inside a `width`-bit value
+fn bit_shift(i: usize, width: usize, order: BitOrder) -> usize {
+    match order {
+        BitOrder::Msb => width - 1 - i,
+        BitOrder::Lsb => i,
+    }
+}
+
+/// Binary to reflected Gray code
+fn gray_encode(value: u8) -> u8 {
+    value ^ (value >> 1)
+}
+
+/// Reflected Gray code back to binary
+fn gray_decode(mut value: u8) -> u8 {
+    let mut shift = value >> 1;
+    while shift != 0 {
+        value ^= shift;
+        shift >>= 1;
+    }
+    value
+}
+
+/// Incremental hex decoder that ignores whitespace and carries an odd
+/// nibble over to the next chunk
+#[derive(Default)]
+struct HexStreamDecoder {
+    nibble: Option<u8>,
+    position: usize,
+}
+impl HexStreamDecoder {
+    fn decode(&mut self, text: &[u8]) -> Result<Vec<u8>, ByteProcError> {
+        let mut out = Vec::with_capacity(text.len() / 2);
+        for &c in text {
+            self.position += 1;
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            let digit = (c as char).to_digit(16).ok_or_else(|| {
+                ByteProcError::HexDecode(format!(
+                    "Invalid character {:?} at position {}",
+                    c as char,
+                    self.position - 1
+                ))
+            })? as u8;
+            match self.nibble.take() {
+                Some(high) => out.push(high << 4 | digit),
+                None => self.nibble = Some(digit),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Drop a pending half byte, e.g. after a bad chunk
+    fn reset(&mut self) {
+        self.nibble = None;
+    }
+
+    fn finish(&self) -> Result<(), ByteProcError> {
+        match self.nibble {
+            Some(_) => Err(ByteProcError::HexDecode("Odd number of digits".into())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reject unpacked bit streams containing anything other than 0 or 1
+fn check_unpacked_bits(bits: &[u8]) -> Result<(), ByteProcError> {
+    match bits.iter().position(|&b| b > 1) {
+        Some(pos) => Err(ByteProcError::InvalidInput {
+            offset: pos,
+            message: format!("expected one bit per byte, got 0x{:02x}", bits[pos]),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Read `n` whole bytes starting at an arbitrary bit offset
+fn bits_at(bits: &[u8], start: usize, n: usize) -> Option<Vec<u8>> {
+    let end = start.checked_add(n.checked_mul(8)?)?;
+    if end > bits.len() {
+        return None;
+    }
+    Some(bits_to_bytes(&bits[start..end], BitOrder::Msb))
+}
+
+/// Encode an unsigned integer into `width` bytes
+fn encode_uint(value: u64, width: usize, endian: Endianness) -> Vec<u8> {
+    let be = value.to_be_bytes();
+    let mut out = be[8 - width..].to_vec();
+    if endian == Endianness::Little {
+        out.reverse();
+    }
+    out
+}
+
+/// Decode an unsigned integer of up to 8 bytes
+fn decode_uint(bytes: &[u8], endian: Endianness) -> u64 {
+    let fold = |acc: u64, &b: &u8| (acc << 8) | b as u64;
+    match endian {
+        Endianness::Big => bytes.iter().fold(0, fold),
+        Endianness::Little => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
         }
-        if cli_args.log_level != default_cli_args.log_level {
-            config_from_file.log_level = cli_args.log_level;
+    }
+    crc
+}
+
+/// CRC-32 (IEEE 802.3, reflected)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Small deterministic PRNG (SplitMix64) for reproducible simulations.
+/// The sequence for a given seed never changes between releases.
+#[derive(Debug)]
+struct SimRng {
+    state: u64,
+}
+impl SimRng {
+    fn new(seed: u64) -> Self {
+        SimRng { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// -------------- Module registry --------------
+
+/// One pipeline entry: a module name, or `{"module": ..., "on_error": ...}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "StageEntry", into = "StageEntry")]
+pub struct StageSpec {
+    pub module: String,
+    /// What happens to the message when this stage fails
+    pub on_error: StageErrorPolicy,
+}
+
+/// Config file form of `StageSpec`; plain names keep the default policy
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StageEntry {
+    Name(String),
+    Stage {
+        module: String,
+        #[serde(default)]
+        on_error: StageErrorPolicy,
+    },
+}
+
+impl From<StageEntry> for StageSpec {
+    fn from(entry: StageEntry) -> Self {
+        match entry {
+            StageEntry::Name(module) => StageSpec { module, on_error: StageErrorPolicy::Fail },
+            StageEntry::Stage { module, on_error } => StageSpec { module, on_error },
+        }
+    }
+}
+
+impl From<StageSpec> for StageEntry {
+    fn from(stage: StageSpec) -> Self {
+        match stage.on_error {
+            StageErrorPolicy::Fail => StageEntry::Name(stage.module),
+            on_error => StageEntry::Stage { module: stage.module, on_error },
+        }
+    }
+}
+
+impl From<&str> for StageSpec {
+    fn from(module: &str) -> Self {
+        StageSpec { module: module.to_string(), on_error: StageErrorPolicy::Fail }
+    }
+}
+
+impl FromStr for StageSpec {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
+/// What a pipeline did with a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineOutcome {
+    /// Output of the last stage
+    Output(Vec<u8>),
+    /// A stage with `on_error: drop` failed; nothing is written
+    Dropped { stage: String },
+}
+
+impl PipelineOutcome {
+    /// The output, or `None` for a dropped message
+    pub fn output(self) -> Option<Vec<u8>> {
+        match self {
+            PipelineOutcome::Output(data) => Some(data),
+            PipelineOutcome::Dropped { .. } => None,
+        }
+    }
+}
+
+/// A module in a registry. `key` names the stage in logs and metadata as
+/// `<list>[<index>].<module>`, so repeated modules stay apart.
+struct Stage {
+    name: &'static str,
+    module: Box<dyn ByteProcessor>,
+    on_error: StageErrorPolicy,
+    key: String,
+}
+
+pub struct ModuleRegistry {
+    modules: Vec<Stage>,
+}
+
+
+/// Build a built-in module by name from its config fields
+pub fn create_module(name: &str, cfg: &Config) -> Result<Box<dyn ByteProcessor>, ByteProcError> {
+    Ok(match name {
+        MODULE_PASSTHROUGH => Box::new(Passthrough),
+        // XOR
+        MODULE_XOR => {
+            let key = cfg.xor_key.as_deref().ok_or_else(|| {
+                ByteProcError::InvalidConfiguration("xor_key must be set if xor is enabled".into())
+            })?;
+            Box::new(XorModule::new(key, cfg.xor_pad_byte())?)
+        }
+        // Base64
+        MODULE_BASE64 => Box::new(Base64Module::new(cfg.base64_encode(), cfg.base64_padding)),
+        // Line coding
+        MODULE_LINECODE => Box::new(LineCodeModule::new(
+            cfg.linecode_scheme,
+            cfg.linecode_encode(),
+            cfg.linecode_unpacked,
+        )),
+        // Bit pack/unpack
+        MODULE_BITS => Box::new(BitsModule::new(
+            cfg.bits_mode == BitsMode::Unpack,
+            cfg.bits_per_byte,
+            cfg.bits_order,
+        )?),
+        // Packet framer/deframer
+        MODULE_FRAMER => Box::new(FramerModule::new(cfg.frame_format()?)),
+        MODULE_DEFRAMER => Box::new(DeframerModule::new(
+            cfg.frame_format()?,
+            cfg.deframer_max_bit_errors,
+            cfg.deframer_search,
+        )?),
+        // Channel simulation
+        MODULE_CHANNEL => Box::new(ChannelModule::new(cfg.channel_params()?)?),
+        // Byte manipulation
+        MODULE_SLICE => Box::new(SliceModule::new(cfg.slice_offset, cfg.slice_length)),
+        MODULE_REVERSE => Box::new(ReverseModule),
+        MODULE_SWAP => Box::new(SwapModule::new(cfg.swap_word_bits)?),
+        MODULE_BITREVERSE => Box::new(BitReverseModule),
+        MODULE_PAD => Box::new(PadModule::new(cfg.pad_mode == PadMode::Pad, cfg.pad_scheme, cfg.pad_block_size)?),
+        MODULE_PREPEND => Box::new(PrependModule::new(&cfg.prepend_hex)?),
+        MODULE_APPEND => Box::new(AppendModule::new(&cfg.append_hex)?),
+        // Bitwise operations
+        MODULE_BITOPS => Box::new(BitopsModule::new(
+            cfg.bitops_op,
+            cfg.bitops_mask.as_deref(),
+            cfg.bitops_amount,
+            cfg.bitops_scope,
+        )?),
+        // Substitution box
+        MODULE_SBOX => Box::new(cfg.sbox()?),
+        // Run-length and Huffman coding
+        MODULE_RLE => Box::new(RleModule::new(cfg.rle_mode == RleMode::Encode)),
+        MODULE_HUFFMAN => Box::new(HuffmanModule::new(cfg.huffman_mode == HuffmanMode::Encode)),
+        // Repetition code
+        MODULE_REPETITION => Box::new(RepetitionModule::new(
+            cfg.repetition_mode == RepetitionMode::Encode,
+            cfg.repetition_count,
+            cfg.repetition_unit,
+        )?),
+        // Symbol mapping
+        MODULE_SYMBOLS => Box::new(SymbolsModule::new(
+            cfg.symbols_mode == SymbolsMode::Map,
+            cfg.symbols_bits,
+            cfg.symbols_gray,
+            cfg.symbols_order,
+        )?),
+        // User script
+        MODULE_SCRIPT => Box::new(cfg.script()?),
+        // WebAssembly sandbox
+        MODULE_WASM => Box::new(cfg.wasm()?),
+        _ => {
+            return Err(ByteProcError::InvalidConfiguration(format!(
+                "Unknown module: {}",
+                name
+            )))
+        }
+    })
+}
+
+impl ModuleRegistry {
+    /// Build the configured pipeline, loading plugins from `plugin_dir`
+    pub fn new(cfg: &Config) -> Result<Self, ByteProcError> {
+        Self::with_plugins(cfg, &cfg.plugins()?)
+    }
+
+    /// Build the configured pipeline with plugins that are already loaded
+    pub fn with_plugins(cfg: &Config, plugins: &[PluginLibrary]) -> Result<Self, ByteProcError> {
+        cfg.validate_stages(plugins)?;
+        let mut registry = Self::with_stages("pipeline", &cfg.stages(), cfg, plugins)?;
+        // Passthrough always present
+        registry.modules.insert(0, Stage::passthrough());
+        Ok(registry)
+    }
+
+    /// Build the given stages in order, taking module settings from `cfg`.
+    /// `list` names the stage list in logs and metadata.
+    pub fn with_stages(
+        list: &str,
+        stages: &[StageSpec],
+        cfg: &Config,
+        plugins: &[PluginLibrary],
+    ) -> Result<Self, ByteProcError> {
+        let mut modules = Vec::new();
+        for (i, stage) in stages.iter().enumerate() {
+            let module: Box<dyn ByteProcessor> = match plugins.iter().find(|p| p.name() == stage.module) {
+                Some(plugin) => Box::new(plugin.instantiate(cfg.plugin_config.get(&stage.module))?),
+                None => create_module(&stage.module, cfg)?,
+            };
+            modules.push(Stage::new(list, i, module, stage.on_error));
+        }
+        Ok(ModuleRegistry { modules })
+    }
+
+    /// Registry that undoes this one: modules in reverse order, each replaced
+    /// by its inverse. Passthrough stays first.
+    pub fn inverse(&self) -> Result<ModuleRegistry, ByteProcError> {
+        let mut modules = vec![Stage::passthrough()];
+        for (i, stage) in self.modules.iter().rev().filter(|stage| stage.name != MODULE_PASSTHROUGH).enumerate() {
+            let inverse = stage
+                .module
+                .as_invertible()
+                .ok_or_else(|| ByteProcError::InvalidConfiguration(format!("module {} has no inverse", stage.name)))?
+                .invert()?;
+            modules.push(Stage::new("inverse", i, inverse, stage.on_error));
+        }
+        Ok(ModuleRegistry { modules })
+    }
+
+    /// Streaming view of the registry. Modules without a streaming version
+    /// buffer the whole message, up to `max_buffer` bytes each.
+    pub fn stream(&self, max_buffer: usize) -> StreamPipeline<'_> {
+        let instance_id = make_instance_id();
+        let stages = self
+            .modules
+            .iter()
+            .map(|Stage { name, module, .. }| match module.stream() {
+                Some(stream) => {
+                    info!("[{}] Streaming with module: {}", instance_id, name);
+                    stream
+                }
+                None => {
+                    info!("[{}] Streaming with module: {} (buffered)", instance_id, name);
+                    Box::new(BufferedStream::new(module.as_ref(), max_buffer)) as Box<dyn StreamProcessor>
+                }
+            })
+            .collect();
+        StreamPipeline { stages }
+    }
+
+    /// process through all enabled modules in insertion order:
+    /// (a dropped message comes back empty)
+    pub fn process_all(
+        &self,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, ByteProcError> {
+        let outcome = self.process_all_with_context(data, &mut ProcessContext::default())?;
+        Ok(outcome.output().unwrap_or_default())
+    }
+
+    /// Like `process_all`, letting modules read and write `ctx`.
+    ///
+    /// A failing stage is handled by its `on_error` policy. The failure is
+    /// recorded as `<key>.error` and `<key>.on_error`, where the key is
+    /// `<list>[<index>].<module>`.
+    pub fn process_all_with_context(
+        &self,
+        mut data: Vec<u8>,
+        ctx: &mut ProcessContext,
+    ) -> Result<PipelineOutcome, ByteProcError> {
+        let instance_id = make_instance_id();
+        for Stage { name, module, on_error, key } in &self.modules {
+            // Records up to the end of this iteration, including the
+            // on_error warning, carry the stage
+            let _stage = LogStage::enter(name);
+            info!(bytes_in = data.len(); "[{}] Processing with module: {}", instance_id, name);
+            let result = module.process_with_context(&data, ctx);
+            if let Ok(out) = &result {
+                info!(bytes_in = data.len(), bytes_out = out.len(); "[{}] Module {} done", instance_id, name);
+            }
+            let err = match result {
+                Ok(out) => {
+                    data = out;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            if *on_error == StageErrorPolicy::Fail {
+                return Err(ByteProcError::Stage { stage: name.to_string(), source: Box::new(err) });
+            }
+            warn!("[{}] Stage {} failed, on_error {}: {}", instance_id, key, on_error, err);
+            ctx.set(&format!("{}.error", key), &err);
+            ctx.set(&format!("{}.on_error", key), on_error);
+            if *on_error == StageErrorPolicy::Drop {
+                return Ok(PipelineOutcome::Dropped { stage: key.clone() });
+            }
+        }
+        Ok(PipelineOutcome::Output(data))
+    }
+}
+
+impl Stage {
+    fn new(list: &str, index: usize, module: Box<dyn ByteProcessor>, on_error: StageErrorPolicy) -> Self {
+        let name = module.name();
+        Stage { name, module, on_error, key: format!("{}[{}].{}", list, index, name) }
+    }
+
+    fn passthrough() -> Self {
+        Stage {
+            name: MODULE_PASSTHROUGH,
+            module: Box::new(Passthrough),
+            on_error: StageErrorPolicy::Fail,
+            key: MODULE_PASSTHROUGH.to_string(),
+        }
+    }
+}
+
+// -------------- Routing --------------
+
+/// Declarative condition on a message, evaluated after the main pipeline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// Payload starts with these hex bytes
+    Prefix(String),
+    /// Regex over the lowercase hex of the payload
+    Regex(String),
+    /// Payload length in bytes, both bounds inclusive
+    Length {
+        #[serde(default)]
+        min: Option<usize>,
+        #[serde(default)]
+        max: Option<usize>,
+    },
+    /// Context metadata key is set, and equals `value` if given
+    Metadata {
+        key: String,
+        #[serde(default)]
+        value: Option<String>,
+    },
+    /// The last 2 (crc16) or 4 (crc32) bytes are a valid CRC of the rest
+    Crc {
+        kind: FrameCrc,
+        #[serde(default)]
+        endian: Endianness,
+    },
+    Not(Box<Predicate>),
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>),
+}
+
+impl Predicate {
+    /// Check the predicate and build its matcher
+    pub fn compile(&self) -> Result<Condition, ByteProcError> {
+        Ok(match self {
+            Predicate::Prefix(hex) => Condition::Prefix(Vec::from_hex(hex).map_err(|e| {
+                ByteProcError::InvalidConfiguration(format!("prefix {}: {}", hex, e))
+            })?),
+            Predicate::Regex(pattern) => Condition::Regex(regex::Regex::new(pattern).map_err(|e| {
+                ByteProcError::InvalidConfiguration(format!("regex {}: {}", pattern, e))
+            })?),
+            Predicate::Length { min, max } => {
+                if let (Some(min), Some(max)) = (min, max)
+                    && min > max
+                {
+                    return Err(ByteProcError::InvalidConfiguration(format!(
+                        "length min {} is greater than max {}", min, max
+                    )));
+                }
+                Condition::Length(min.unwrap_or(0), max.unwrap_or(usize::MAX))
+            }
+            Predicate::Metadata { key, value } => Condition::Metadata(key.clone(), value.clone()),
+            Predicate::Crc { kind, endian } => {
+                if *kind == FrameCrc::None {
+                    return Err(ByteProcError::InvalidConfiguration(
+                        "crc predicate needs crc16 or crc32".into(),
+                    ));
+                }
+                Condition::Crc(*kind, *endian)
+            }
+            Predicate::Not(inner) => Condition::Not(Box::new(inner.compile()?)),
+            Predicate::All(list) => Condition::All(list.iter().map(Predicate::compile).collect::<Result<_, _>>()?),
+            Predicate::Any(list) => Condition::Any(list.iter().map(Predicate::compile).collect::<Result<_, _>>()?),
+        })
+    }
+}
+
+/// Compiled form of a `Predicate`
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Prefix(Vec<u8>),
+    Regex(regex::Regex),
+    Length(usize, usize),
+    Metadata(String, Option<String>),
+    Crc(FrameCrc, Endianness),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn matches(&self, data: &[u8], ctx: &ProcessContext) -> bool {
+        match self {
+            Condition::Prefix(prefix) => data.starts_with(prefix),
+            Condition::Regex(re) => re.is_match(&hex::encode(data)),
+            Condition::Length(min, max) => (*min..=*max).contains(&data.len()),
+            Condition::Metadata(key, value) => match (ctx.get(key), value) {
+                (Some(actual), Some(expected)) => actual == expected,
+                (found, None) => found.is_some(),
+                (None, Some(_)) => false,
+            },
+            Condition::Crc(kind, endian) => {
+                let width = if *kind == FrameCrc::Crc16 { 2 } else { 4 };
+                if data.len() < width {
+                    return false;
+                }
+                let (body, trailer) = data.split_at(data.len() - width);
+                let expected = match kind {
+                    FrameCrc::Crc16 => crc16_ccitt(body) as u64,
+                    _ => crc32(body) as u64,
+                };
+                decode_uint(trailer, *endian) == expected
+            }
+            Condition::Not(inner) => !inner.matches(data, ctx),
+            Condition::All(list) => list.iter().all(|c| c.matches(data, ctx)),
+            Condition::Any(list) => list.iter().any(|c| c.matches(data, ctx)),
+        }
+    }
+}
+
+/// A routing rule: messages matching `when` go through the named
+/// sub-pipeline and on to the named outputs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RouteSpec {
+    /// Label used in logs and the `router.route` metadata
+    #[serde(default)]
+    pub name: Option<String>,
+    pub when: Predicate,
+    /// Entry of `pipelines` to run after the main pipeline
+    #[serde(default)]
+    pub pipeline: Option<String>,
+    /// Names of outputs that get the message; empty means all outputs
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+struct Route {
+    label: String,
+    condition: Condition,
+    pipeline: Option<ModuleRegistry>,
+    outputs: Vec<String>,
+}
+
+/// Sends each message down the first route whose predicate matches.
+/// Messages that match no route are passed on unchanged to all outputs.
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    /// Build the routes; sub-pipelines may use any of `plugins`
+    pub fn new(cfg: &Config, plugins: &[PluginLibrary]) -> Result<Self, ByteProcError> {
+        let mut routes = Vec::new();
+        for (i, spec) in cfg.routes.iter().enumerate() {
+            let pipeline = match &spec.pipeline {
+                Some(name) => {
+                    let stages = cfg.pipelines.get(name).ok_or_else(|| {
+                        ByteProcError::InvalidConfiguration(format!("routes[{}]: unknown pipeline {}", i, name))
+                    })?;
+                    Some(ModuleRegistry::with_stages(&format!("pipelines.{}", name), stages, cfg, plugins)?)
+                }
+                None => None,
+            };
+            routes.push(Route {
+                label: spec.name.clone().unwrap_or_else(|| format!("routes[{}]", i)),
+                condition: spec.when.compile()?,
+                pipeline,
+                outputs: spec.outputs.clone(),
+            });
+        }
+        Ok(Router { routes })
+    }
+
+    /// Run the matching route's sub-pipeline and return the result with the
+    /// names of the outputs it should go to (empty = all)
+    pub fn dispatch(
+        &self,
+        data: Vec<u8>,
+        ctx: &mut ProcessContext,
+    ) -> Result<(PipelineOutcome, &[String]), ByteProcError> {
+        let instance_id = make_instance_id();
+        let Some(route) = self.routes.iter().find(|r| r.condition.matches(&data, ctx)) else {
+            if !self.routes.is_empty() {
+                info!("[{}] No route matched", instance_id);
+            }
+            return Ok((PipelineOutcome::Output(data), &[]));
+        };
+        info!("[{}] Routing message via {}", instance_id, route.label);
+        ctx.set("router.route", &route.label);
+        let outcome = match &route.pipeline {
+            Some(pipeline) => pipeline.process_all_with_context(data, ctx)?,
+            None => PipelineOutcome::Output(data),
+        };
+        Ok((outcome, &route.outputs))
+    }
+}
+
+// -------------- Inputs and outputs --------------
+
+/// One output of a fan-out configuration
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputSpec {
+    /// Name that routes use to pick this output
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub output_type: OutputType,
+    /// ZMQ endpoint for zmq_push
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Bind the PUSH socket instead of connecting
+    #[serde(default)]
+    pub bind: bool,
+    /// File for file outputs
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub on_error: SinkErrorPolicy,
+}
+
+/// One PULL endpoint of a fan-in configuration
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputSpec {
+    pub endpoint: String,
+    /// Bind the PULL socket instead of connecting
+    #[serde(default)]
+    pub bind: bool,
+}
+
+/// Destination for processed messages
+pub trait Sink {
+    fn name(&self) -> String;
+    fn send(&mut self, hex: &str, ctx: &ProcessContext) -> Result<(), ByteProcError>;
+}
+
+/// Prints each message as a hex line on stdout
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn name(&self) -> String {
+        "stdout".into()
+    }
+
+    fn send(&mut self, hex: &str, _ctx: &ProcessContext) -> Result<(), ByteProcError> {
+        println!("{}", hex);
+        Ok(())
+    }
+}
+
+/// Appends each message as a hex line to a file
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    pub fn new(path: &Path) -> Result<Self, ByteProcError> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| ByteProcError::Io(format!("{}: {}", path.display(), e)))?;
+        Ok(Self { path: path.to_path_buf(), file })
+    }
+}
+
+impl Sink for FileSink {
+    fn name(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    fn send(&mut self, hex: &str, _ctx: &ProcessContext) -> Result<(), ByteProcError> {
+        writeln!(self.file, "{}", hex)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| ByteProcError::Io(format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+/// Sends each message on a ZMQ PUSH socket, optionally followed by its context frame
+pub struct ZmqPushSink {
+    endpoint: String,
+    socket: Socket,
+    send_context: bool,
+}
+
+impl ZmqPushSink {
+    pub fn new(context: &Context, endpoint: &str, bind: bool, cfg: &Config) -> Result<Self, ByteProcError> {
+        let socket = open_zmq_socket(context, zmq::PUSH, endpoint, bind, cfg)?;
+        Ok(Self { endpoint: endpoint.to_string(), socket, send_context: cfg.zmq_send_context })
+    }
+}
+
+impl Sink for ZmqPushSink {
+    fn name(&self) -> String {
+        format!("zmq_push:{}", self.endpoint)
+    }
+
+    fn send(&mut self, hex: &str, ctx: &ProcessContext) -> Result<(), ByteProcError> {
+        if self.send_context {
+            self.socket.send(hex, zmq::SNDMORE)
+                .and_then(|_| self.socket.send(&ctx.to_json(), 0))
+                .map_err(|e| ByteProcError::Zmq(e.to_string()))
+        } else {
+            self.socket.send(hex, 0)
+                .map_err(|e| ByteProcError::Zmq(e.to_string()))
+        }
+    }
+}
+
+/// Create a PULL or PUSH socket with the configured ZMQ options
+fn open_zmq_socket(
+    context: &Context,
+    kind: zmq::SocketType,
+    endpoint: &str,
+    bind: bool,
+    cfg: &Config,
+) -> Result<Socket, ByteProcError> {
+    let instance_id = make_instance_id();
+    let kind_name = match kind {
+        zmq::PULL => "PULL",
+        zmq::PUB => "PUB",
+        zmq::SUB => "SUB",
+        _ => "PUSH",
+    };
+    let zmq_err = |e: zmq::Error| ByteProcError::Zmq(format!("{} {}: {}", kind_name, endpoint, e));
+
+    let sock = context.socket(kind).map_err(zmq_err)?;
+    sock.set_reconnect_ivl(cfg.zmq_reconnect_interval_ms as i32).map_err(zmq_err)?;
+    sock.set_reconnect_ivl_max(cfg.zmq_max_reconnect_attempts as i32).map_err(zmq_err)?;
+    if kind == zmq::SUB {
+        sock.set_subscribe(b"").map_err(zmq_err)?;
+    }
+    if kind == zmq::PULL || kind == zmq::SUB {
+        sock.set_rcvtimeo(cfg.zmq_receive_timeout_ms).map_err(zmq_err)?;
+    } else {
+        sock.set_sndtimeo(cfg.zmq_send_timeout_ms).map_err(zmq_err)?;
+    }
+    sock.set_linger(cfg.zmq_linger_ms).map_err(zmq_err)?;
+
+    if bind {
+        info!("[{}] Binding {} socket to {}", instance_id, kind_name, endpoint);
+        sock.bind(endpoint).map_err(zmq_err)?;
+    } else {
+        info!("[{}] Connecting {} socket to {}", instance_id, kind_name, endpoint);
+        sock.connect(endpoint).map_err(zmq_err)?;
+    }
+    Ok(sock)
+}
+
+/// Delivers every message to all configured outputs
+pub struct FanOut {
+    sinks: Vec<(Option<String>, Box<dyn Sink>, SinkErrorPolicy)>,
+}
+
+impl FanOut {
+    pub fn new() -> Self {
+        Self { sinks: Vec::new() }
+    }
+
+    /// Open a sink for each output spec
+    pub fn open(context: &Context, specs: &[OutputSpec], cfg: &Config) -> Result<Self, ByteProcError> {
+        let mut fan_out = Self::new();
+        for spec in specs {
+            let sink: Box<dyn Sink> = match spec.output_type {
+                OutputType::Stdout => Box::new(StdoutSink),
+                OutputType::ZmqPush => {
+                    let endpoint = spec.endpoint.as_deref().ok_or_else(|| {
+                        ByteProcError::InvalidConfiguration("endpoint must be set for zmq_push".into())
+                    })?;
+                    Box::new(ZmqPushSink::new(context, endpoint, spec.bind, cfg)?)
+                }
+                OutputType::File => {
+                    let path = spec.path.as_deref().ok_or_else(|| {
+                        ByteProcError::InvalidConfiguration("path must be set for file".into())
+                    })?;
+                    Box::new(FileSink::new(path)?)
+                }
+            };
+            fan_out.sinks.push((spec.name.clone(), sink, spec.on_error));
+        }
+        Ok(fan_out)
+    }
+
+    pub fn add(&mut self, sink: Box<dyn Sink>, on_error: SinkErrorPolicy) {
+        self.sinks.push((None, sink, on_error));
+    }
+
+    /// Like `add`, with a name that routes can refer to
+    pub fn add_named(&mut self, name: &str, sink: Box<dyn Sink>, on_error: SinkErrorPolicy) {
+        self.sinks.push((Some(name.to_string()), sink, on_error));
+    }
+
+    /// Send to every sink in order and return how many took the message.
+    /// A failing `warn` sink is logged and skipped; a failing `fail` sink
+    /// stops delivery and returns its error.
+    pub fn send(&mut self, hex: &str, ctx: &ProcessContext) -> Result<usize, ByteProcError> {
+        self.send_to(hex, ctx, &[])
+    }
+
+    /// Like `send`, limited to the sinks named in `names` (empty = all)
+    pub fn send_to(&mut self, hex: &str, ctx: &ProcessContext, names: &[String]) -> Result<usize, ByteProcError> {
+        let instance_id = make_instance_id();
+        let mut delivered = 0;
+        for (name, sink, on_error) in self.sinks.iter_mut() {
+            if !names.is_empty() && !name.as_ref().is_some_and(|n| names.contains(n)) {
+                continue;
+            }
+            info!("[{}] Writing output to {}", instance_id, sink.name());
+            match sink.send(hex, ctx) {
+                Ok(()) => delivered += 1,
+                Err(e) if *on_error == SinkErrorPolicy::Warn => {
+                    warn!("[{}] Output {} failed, continuing: {}", instance_id, sink.name(), e);
+                }
+                Err(e) => {
+                    error!("[{}] Output {} failed: {}", instance_id, sink.name(), e);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// True if any sink sends over ZMQ
+    pub fn has_zmq(&self) -> bool {
+        self.sinks.iter().any(|(_, sink, _)| sink.name().starts_with("zmq_push:"))
+    }
+}
+
+impl Default for FanOut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A message that failed, with what went wrong, as written to the
+/// dead-letter output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    pub instance_id: String,
+    /// When the message failed (µs since the Unix epoch)
+    pub time_us: u64,
+    /// `ByteProcError::kind` of the failure
+    pub error_kind: String,
+    pub error: String,
+    /// The hex input exactly as received, ready to replay
+    pub input: String,
+    pub context: ProcessContext,
+}
+
+impl DeadLetter {
+    pub fn new(input: &str, ctx: &ProcessContext, error: &ByteProcError) -> Self {
+        DeadLetter {
+            instance_id: make_instance_id().to_string(),
+            time_us: unix_time_us(),
+            error_kind: error.kind().to_string(),
+            error: error.to_string(),
+            input: input.to_string(),
+            context: ctx.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Open the dead-letter file and/or ZMQ socket from the config
+pub fn open_dead_letters(context: &Context, cfg: &Config) -> Result<FanOut, ByteProcError> {
+    let mut dead_letters = FanOut::new();
+    if let Some(path) = &cfg.dead_letter_file {
+        dead_letters.add(Box::new(FileSink::new(path)?), SinkErrorPolicy::Fail);
+    }
+    if let Some(endpoint) = &cfg.dead_letter_zmq_socket {
+        // One JSON frame per dead letter, the context is already inside it
+        let sink = ZmqPushSink {
+            endpoint: endpoint.clone(),
+            socket: open_zmq_socket(context, zmq::PUSH, endpoint, cfg.dead_letter_zmq_bind, cfg)?,
+            send_context: false,
+        };
+        dead_letters.add(Box::new(sink), SinkErrorPolicy::Fail);
+    }
+    Ok(dead_letters)
+}
+
+/// Apply `error_policy` to a message that failed. Returns the error for
+/// `abort`, or if the dead letter itself can't be written.
+pub fn handle_failed_message(
+    policy: ErrorPolicy,
+    dead_letters: &mut FanOut,
+    input: &str,
+    ctx: &ProcessContext,
+    error: ByteProcError,
+) -> Result<(), ByteProcError> {
+    let instance_id = make_instance_id();
+    match policy {
+        ErrorPolicy::Abort => Err(error),
+        ErrorPolicy::Skip => {
+            warn!("[{}] Skipping message {}: {}", instance_id, ctx.sequence, error);
+            Ok(())
+        }
+        ErrorPolicy::DeadLetter => {
+            warn!("[{}] Dead-lettering message {}: {}", instance_id, ctx.sequence, error);
+            let letter = DeadLetter::new(input, ctx, &error);
+            dead_letters.send(&letter.to_json(), ctx)?;
+            Ok(())
+        }
+    }
+}
+
+/// A message taken from one of the fan-in endpoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedMessage {
+    pub endpoint: String,
+    pub hex: String,
+    /// Context sent by the upstream instance as a second frame, if any
+    pub context: Option<ProcessContext>,
+}
+
+/// Merges several PULL endpoints into one stream of messages
+pub struct FanIn {
+    sockets: Vec<(String, Socket)>,
+    next: usize,
+}
+
+impl FanIn {
+    pub fn open(context: &Context, specs: &[InputSpec], cfg: &Config) -> Result<Self, ByteProcError> {
+        let sockets = specs
+            .iter()
+            .map(|spec| {
+                open_zmq_socket(context, zmq::PULL, &spec.endpoint, spec.bind, cfg)
+                    .map(|sock| (spec.endpoint.clone(), sock))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { sockets, next: 0 })
+    }
+
+    /// Wait up to `timeout_ms` (-1 = forever) for a message on any endpoint.
+    /// Endpoints are served round-robin so a busy one cannot starve the rest.
+    pub fn recv(&mut self, timeout_ms: i64) -> Result<Option<ReceivedMessage>, ByteProcError> {
+        let instance_id = make_instance_id();
+        let zmq_err = |e: zmq::Error| ByteProcError::Zmq(e.to_string());
+
+        let mut items: Vec<zmq::PollItem> = self
+            .sockets
+            .iter()
+            .map(|(_, sock)| sock.as_poll_item(zmq::POLLIN))
+            .collect();
+        if zmq::poll(&mut items, timeout_ms).map_err(zmq_err)? == 0 {
+            return Ok(None);
+        }
+        let count = self.sockets.len();
+        let Some(idx) = (0..count)
+            .map(|i| (self.next + i) % count)
+            .find(|&i| items[i].is_readable())
+        else {
+            return Ok(None);
+        };
+        self.next = (idx + 1) % count;
+
+        let (endpoint, sock) = &self.sockets[idx];
+        let msg = sock.recv_msg(0).map_err(|e| {
+            error!("[{}] ZMQ recv_msg error: {}", instance_id, e);
+            zmq_err(e)
+        })?;
+        info!("[{}] Received ZMQ message ({} bytes) from {}", instance_id, msg.len(), endpoint);
+
+        // Invalid UTF-8 is kept (lossily) so it fails hex decoding and
+        // follows the error policy like any other bad input
+        let hex = match msg.as_str() {
+            Some(s) => s.trim().to_string(),
+            None => {
+                warn!("[{}] ZMQ message is not valid UTF-8", instance_id);
+                String::from_utf8_lossy(&msg).trim().to_string()
+            }
+        };
+
+        // Upstream instances may send their context as a second frame
+        let context = if sock.get_rcvmore().map_err(zmq_err)? {
+            let frame = sock.recv_bytes(0).map_err(zmq_err)?;
+            match ProcessContext::from_json(&frame) {
+                Ok(upstream) => Some(upstream),
+                Err(e) => {
+                    warn!("[{}] Ignoring message context: {}", instance_id, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Some(ReceivedMessage { endpoint: endpoint.clone(), hex, context }))
+    }
+}
+
+// -------------- Logging --------------
+
+thread_local! {
+    // Stage and message the current thread's log records belong to
+    static LOG_SCOPE: RefCell<(Option<&'static str>, Option<u64>)> = const { RefCell::new((None, None)) };
+}
+
+/// Tags this thread's log records with a pipeline stage until dropped
+struct LogStage;
+
+impl LogStage {
+    fn enter(stage: &'static str) -> Self {
+        LOG_SCOPE.with_borrow_mut(|scope| scope.0 = Some(stage));
+        LogStage
+    }
+}
+
+impl Drop for LogStage {
+    fn drop(&mut self) {
+        LOG_SCOPE.with_borrow_mut(|scope| scope.0 = None);
+    }
+}
+
+fn set_log_message(message_id: Option<u64>) {
+    LOG_SCOPE.with_borrow_mut(|scope| scope.1 = message_id);
+}
+
+/// Format microseconds since the Unix epoch as UTC ISO 8601,
+/// e.g. `2024-05-01T12:00:00.000123Z`
+pub fn iso8601_utc(time_us: u64) -> String {
+    let secs = time_us / 1_000_000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year, month, day, rem / 3600, rem / 60 % 60, rem % 60, time_us % 1_000_000
+    )
+}
+
+/// One structured log line
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    /// UTC ISO 8601
+    pub timestamp: String,
+    pub level: String,
+    pub instance_id: String,
+    pub pid: u32,
+    /// Pipeline stage running when the record was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    /// Sequence number of the message being processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<u64>,
+    pub message: String,
+    /// Key/values attached to the log call, e.g. byte counts
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl LogRecord {
+    pub fn new(record: &Record) -> Self {
+        let instance_id = make_instance_id();
+        let message = record.args().to_string();
+        // The text format prefixes every message with the instance ID
+        let prefix = format!("[{}] ", instance_id);
+        let message = message.strip_prefix(&prefix).map(str::to_string).unwrap_or(message);
+        let (stage, message_id) = LOG_SCOPE.with_borrow(|scope| *scope);
+        let mut fields = LogFields(BTreeMap::new());
+        let _ = record.key_values().visit(&mut fields);
+        LogRecord {
+            timestamp: iso8601_utc(unix_time_us()),
+            level: record.level().to_string(),
+            instance_id: instance_id.to_string(),
+            pid: std::process::id(),
+            stage: stage.map(str::to_string),
+            message_id,
+            message,
+            fields: fields.0,
         }
-        if cli_args.log_file != default_cli_args.log_file {
-            config_from_file.log_file = cli_args.log_file;
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// One text line: timestamp, level, instance, stage, message id, message, fields
+    pub fn to_text(&self) -> String {
+        let mut line = format!("{} {:<5} [{}]", self.timestamp, self.level, self.instance_id);
+        if let Some(stage) = &self.stage {
+            line.push_str(&format!(" [{}]", stage));
         }
-        if cli_args.log_append != default_cli_args.log_append {
-            config_from_file.log_append = cli_args.log_append;
+        if let Some(message_id) = self.message_id {
+            line.push_str(&format!(" #{}", message_id));
         }
-        if cli_args.xor_enabled != default_cli_args.xor_enabled {
-            config_from_file.xor_enabled = cli_args.xor_enabled;
+        line.push_str(&format!(" {}", self.message));
+        for (key, value) in &self.fields {
+            line.push_str(&format!(" {}={}", key, value));
         }
-        if cli_args.xor_key.is_some() {
-            config_from_file.xor_key = cli_args.xor_key;
+        line
+    }
+}
+
+// Collects log call key/values as JSON values
+struct LogFields(BTreeMap<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for LogFields {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        let json = if let Some(n) = value.to_u64() {
+            n.into()
+        } else if let Some(n) = value.to_i64() {
+            n.into()
+        } else if let Some(b) = value.to_bool() {
+            b.into()
+        } else if let Some(x) = value.to_f64() {
+            x.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.to_string(), json);
+        Ok(())
+    }
+}
+
+/// Log file shared by several instances, rotated by size to
+/// `<path>.1` .. `<path>.N`.
+///
+/// Complete lines are written while holding an exclusive lock on
+/// `<path>.lock`, so instances never interleave partial lines or rotate
+/// under each other. When another instance has rotated the file, the path
+/// points to a new inode and the file is reopened before writing.
+pub struct RotatingLogFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    max_bytes: u64,
+    count: u32,
+    file: File,
+    // Bytes up to the last complete line
+    pending: Vec<u8>,
+}
+
+impl RotatingLogFile {
+    pub fn open(path: &Path, max_bytes: u64, count: u32, append: bool) -> Result<Self, ByteProcError> {
+        let io_err = |e: io::Error| ByteProcError::Io(format!("{}: {}", path.display(), e));
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let log = RotatingLogFile {
+            path: path.to_path_buf(),
+            lock_path: PathBuf::from(lock_path),
+            max_bytes,
+            count,
+            file: OpenOptions::new().append(true).create(true).open(path).map_err(io_err)?,
+            pending: Vec::new(),
+        };
+        if !append {
+            let lock = log.lock().map_err(io_err)?;
+            log.file.set_len(0).map_err(io_err)?;
+            drop(lock);
         }
-        if cli_args.xor_pad != default_cli_args.xor_pad {
-            config_from_file.xor_pad = cli_args.xor_pad;
+        Ok(log)
+    }
+
+    fn lock(&self) -> io::Result<File> {
+        let lock = OpenOptions::new().write(true).create(true).truncate(false).open(&self.lock_path)?;
+        lock.lock()?;
+        Ok(lock)
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = OpenOptions::new().append(true).create(true).open(&self.path)?;
+        Ok(())
+    }
+
+    /// Shift `<path>.i` to `<path>.i+1`, dropping the oldest, then move the
+    /// current file to `<path>.1`
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |i: u32| {
+            let mut name = self.path.as_os_str().to_owned();
+            name.push(format!(".{}", i));
+            PathBuf::from(name)
+        };
+        for i in (1..self.count).rev() {
+            if numbered(i).exists() {
+                std::fs::rename(numbered(i), numbered(i + 1))?;
+            }
         }
-        if cli_args.base64_enabled != default_cli_args.base64_enabled {
-            config_from_file.base64_enabled = cli_args.base64_enabled;
+        std::fs::rename(&self.path, numbered(1))?;
+        self.reopen()
+    }
+
+    /// Write complete lines under the lock, rotating first if needed
+    fn write_lines(&mut self, lines: &[u8]) -> io::Result<()> {
+        let lock = self.lock()?;
+        match std::fs::metadata(&self.path) {
+            Ok(meta) if same_file(&meta, &self.file.metadata()?) => {}
+            _ => self.reopen()?,
         }
-        if cli_args.base64_mode != default_cli_args.base64_mode {
-            config_from_file.base64_mode = cli_args.base64_mode;
+        let size = self.file.metadata()?.len();
+        if self.max_bytes > 0 && size > 0 && size + lines.len() as u64 > self.max_bytes {
+            self.rotate()?;
         }
-        if cli_args.base64_padding != default_cli_args.base64_padding {
-            config_from_file.base64_padding = cli_args.base64_padding;
+        self.file.write_all(lines)?;
+        drop(lock);
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') {
+            let lines: Vec<u8> = self.pending.drain(..=end).collect();
+            self.write_lines(&lines)?;
         }
+        Ok(buf.len())
+    }
 
-        // The config path itself from CLI should always override
-        if cli_args.config.is_some() {
-            config_from_file.config = cli_args.config;
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let rest = std::mem::take(&mut self.pending);
+            self.write_lines(&rest)?;
         }
-        
-        // Validate the final configuration
-        config_from_file.validate()?;
-        
-        Ok(config_from_file)
+        self.file.flush()
     }
-    
-    /// Load configuration from a file
-    fn from_file(path: &PathBuf) -> Result<Self, ByteProcError> {
-        let file = File::open(path)
-            .map_err(|e| ByteProcError::Io(format!("Failed to open config file: {}", e)))?;
-            
-        let mut reader = io::BufReader::new(file);
-        let mut contents = String::new();
-        reader.read_to_string(&mut contents)
-            .map_err(|e| ByteProcError::Io(format!("Failed to read config file: {}", e)))?;
-            
-        serde_json::from_str(&contents)
-            .map_err(|e| ByteProcError::Io(format!("Failed to parse config file: {}", e)))
+}
+
+impl Drop for RotatingLogFile {
+    fn drop(&mut self) {
+        let _ = self.flush();
     }
-    
-    /// Validate the configuration
-    pub fn validate(&self) -> Result<(), ByteProcError> {
-        // Check required fields for specific input/output types
-        if self.input_type == InputType::ZmqPull && self.input_zmq_socket.is_none() {
-            return Err(ByteProcError::InvalidConfiguration(
-                "input_zmq_socket must be set for zmq_pull".into(),
-            ));
+}
+
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    // No inode to compare; a rotated file has been truncated or recreated
+    a.len() >= b.len() && a.created().ok() == b.created().ok()
+}
+
+/// Logger writing one `LogRecord` JSON object per line
+struct JsonLogger {
+    level: LevelFilter,
+    file: Mutex<RotatingLogFile>,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with("mio")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
         }
-        
-        if self.output_type == OutputType::ZmqPush && self.output_zmq_socket.is_none() {
-            return Err(ByteProcError::InvalidConfiguration(
-                "output_zmq_socket must be set for zmq_push".into(),
-            ));
+        let line = LogRecord::new(record).to_json();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", line);
         }
-        
-        if self.xor_enabled && self.xor_key.is_none() {
-            return Err(ByteProcError::InvalidConfiguration(
-                "xor_key must be set if xor_enabled".into(),
-            ));
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
         }
-        
-        Ok(())
     }
 }
 
-// -------------- Helpers --------------
-// Static instance ID initialized on first access
-static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+impl SharedLogger for JsonLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
 
-/// Generate a unique instance identifier for logging
-/// The ID is generated only once per process and then reused
-fn make_instance_id() -> &'static str {
-    INSTANCE_ID.get_or_init(|| {
-        format!("pid-{}-{:x}", 
-            std::process::id(), 
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_micros() & 0xFFFF)
-    })
-}
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
 
-// -------------- Module registry --------------
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        self
+    }
+}
 
-pub struct ModuleRegistry {
-    modules: HashMap<&'static str, Box<dyn ByteProcessor>>,
+/// Logger publishing every record as `LogRecord` JSON on a ZMQ PUSH or PUB
+/// socket. Sending never blocks; records are dropped when the queue is full.
+struct ZmqLogger {
+    level: LevelFilter,
+    socket: Mutex<Socket>,
+    // Records queued since the last flush
+    pending: AtomicBool,
 }
 
+impl ZmqLogger {
+    fn open(level: LevelFilter, endpoint: &str, cfg: &Config) -> Result<Self, ByteProcError> {
+        let kind = match cfg.log_zmq_type {
+            LogZmqType::Push => zmq::PUSH,
+            LogZmqType::Pub => zmq::PUB,
+        };
+        // The socket keeps its context alive
+        let socket = open_zmq_socket(&Context::new(), kind, endpoint, false, cfg)?;
+        Ok(ZmqLogger { level, socket: Mutex::new(socket), pending: AtomicBool::new(false) })
+    }
+}
 
-impl ModuleRegistry {
-    pub fn new(cfg: &Config) -> Result<Self, ByteProcError> {
-        let mut modules: HashMap<&'static str, Box<dyn ByteProcessor>> = HashMap::new();
-        // Passthrough always present
-        modules.insert(MODULE_PASSTHROUGH, Box::new(Passthrough));
+impl Log for ZmqLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with("mio")
+    }
 
-        // XOR
-        if cfg.xor_enabled {
-            let m = XorModule::new(
-                cfg.xor_key.as_ref().unwrap(),
-                cfg.xor_pad_byte(),
-            )?;
-            modules.insert(MODULE_XOR, Box::new(m));
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
         }
+        let line = LogRecord::new(record).to_json();
+        if let Ok(socket) = self.socket.lock()
+            && socket.send(line.as_bytes(), zmq::DONTWAIT).is_ok()
+        {
+            self.pending.store(true, Ordering::Relaxed);
+        }
+    }
 
-        // Base64
-        if cfg.base64_enabled {
-            let m = Base64Module::new(cfg.base64_encode(), cfg.base64_padding);
-            modules.insert(MODULE_BASE64, Box::new(m));
+    fn flush(&self) {
+        // Like the ZMQ outputs: give queued records time to leave before exit
+        if self.pending.swap(false, Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
         }
+    }
+}
 
-        Ok(ModuleRegistry { modules })
+impl SharedLogger for ZmqLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
     }
 
-    /// process through all enabled modules in insertion order:
-    pub fn process_all(
-        &self,
-        mut data: Vec<u8>,
-    ) -> Result<Vec<u8>, ByteProcError> {
-        let instance_id = make_instance_id();
-        for (name, module) in &self.modules {
-            info!("[{}] Processing with module: {}", instance_id, name);
-            data = module.process(&data)?;
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        self
+    }
+}
+
+/// Receive log records published by other instances on `log_zmq_socket`
+/// and write them to `out` ordered by timestamp, one per line.
+///
+/// Records are held for `args.window_ms` so that records from several
+/// instances arriving slightly out of order are merged in order. Returns
+/// the number of records written once `args.idle_ms` passes without
+/// records.
+pub fn collect_logs(cfg: &Config, args: &LogCollectorArgs, out: &mut dyn Write) -> Result<u64, ByteProcError> {
+    let instance_id = make_instance_id();
+    let endpoint = cfg.log_zmq_socket.as_deref().ok_or_else(|| {
+        ByteProcError::InvalidConfiguration("log_zmq_socket must be set for log-collector".into())
+    })?;
+    let kind = match cfg.log_zmq_type {
+        LogZmqType::Push => zmq::PULL,
+        LogZmqType::Pub => zmq::SUB,
+    };
+    let socket = open_zmq_socket(&Context::new(), kind, endpoint, true, cfg)?;
+    let io_err = |e: io::Error| ByteProcError::Io(e.to_string());
+
+    // Keyed by timestamp, then arrival, so equal timestamps keep their order
+    let mut pending: BTreeMap<(String, u64), LogRecord> = BTreeMap::new();
+    let mut received: u64 = 0;
+    let mut written: u64 = 0;
+    let mut last_received = Instant::now();
+    loop {
+        let ready = socket.poll(zmq::POLLIN, 100)
+            .map_err(|e| ByteProcError::Zmq(format!("{}: {}", endpoint, e)))?;
+        if ready > 0 {
+            while let Ok(bytes) = socket.recv_bytes(zmq::DONTWAIT) {
+                last_received = Instant::now();
+                match serde_json::from_slice::<LogRecord>(&bytes) {
+                    Ok(record) => {
+                        pending.insert((record.timestamp.clone(), received), record);
+                        received += 1;
+                    }
+                    Err(e) => warn!("[{}] Ignoring malformed log record: {}", instance_id, e),
+                }
+            }
+        }
+
+        let idle = args.idle_ms > 0 && last_received.elapsed() >= Duration::from_millis(args.idle_ms);
+        // Timestamps share one fixed-width format, so they order as strings
+        let cutoff = iso8601_utc(unix_time_us().saturating_sub(args.window_ms * 1000));
+        while let Some(entry) = pending.first_entry() {
+            if !idle && entry.key().0 > cutoff {
+                break;
+            }
+            let record = entry.remove();
+            let line = if args.json { record.to_json() } else { record.to_text() };
+            writeln!(out, "{}", line).map_err(io_err)?;
+            written += 1;
+        }
+        out.flush().map_err(io_err)?;
+
+        if idle {
+            info!(records = written; "[{}] No log records for {}ms, stopping", instance_id, args.idle_ms);
+            return Ok(written);
         }
-        Ok(data)
     }
 }
 
 // -------------- Main --------------
 
+/// Stream hex text from `input` through the registry to `output`, reading
+/// `chunk_size` bytes at a time. Memory stays bounded by the chunk size for
+/// modules that stream; the rest buffer up to `max_buffer` bytes each.
+pub fn stream_hex(
+    registry: &ModuleRegistry,
+    max_buffer: usize,
+    chunk_size: usize,
+    input: impl Read,
+    output: impl Write,
+) -> Result<(), ByteProcError> {
+    stream_hex_with_policy(registry, max_buffer, chunk_size, input, output, ErrorPolicy::Abort, &mut FanOut::new())
+}
+
+/// `stream_hex` with an error policy. A chunk that fails to decode or
+/// process is skipped or dead-lettered as the hex text read for it, and
+/// streaming goes on with fresh module state. A failure while flushing at
+/// the end has no chunk to keep, so its dead letter has empty input.
+pub fn stream_hex_with_policy(
+    registry: &ModuleRegistry,
+    max_buffer: usize,
+    chunk_size: usize,
+    mut input: impl Read,
+    mut output: impl Write,
+    policy: ErrorPolicy,
+    dead_letters: &mut FanOut,
+) -> Result<(), ByteProcError> {
+    let instance_id = make_instance_id();
+    let mut pipeline = registry.stream(max_buffer);
+    let mut decoder = HexStreamDecoder::default();
+    // Two hex characters per byte
+    let mut text = vec![0u8; chunk_size.max(1) * 2];
+    let (mut bytes_in, mut bytes_out) = (0usize, 0usize);
+    let io_err = |e: io::Error| ByteProcError::Io(e.to_string());
+
+    // Each chunk is numbered like a message, with its offset in the input
+    let (mut chunks, mut offset) = (0u64, 0usize);
+    let mut chunk_context = |offset: usize| {
+        let mut ctx = ProcessContext::new(message_sequence(chunks), "stdin");
+        ctx.set("stream.offset", offset);
+        chunks += 1;
+        ctx
+    };
+
+    loop {
+        let n = input.read(&mut text).map_err(io_err)?;
+        if n == 0 {
+            break;
+        }
+        let result = decoder.decode(&text[..n]).and_then(|bytes| {
+            bytes_in += bytes.len();
+            pipeline.update(&bytes)
+        });
+        match result {
+            Ok(out) => {
+                bytes_out += out.len();
+                output.write_all(hex::encode(&out).as_bytes()).map_err(io_err)?;
+            }
+            Err(e) => {
+                let chunk = String::from_utf8_lossy(&text[..n]);
+                handle_failed_message(policy, dead_letters, chunk.trim(), &chunk_context(offset), e)?;
+                decoder.reset();
+                pipeline = registry.stream(max_buffer);
+            }
+        }
+        offset += n;
+    }
+    let out = match decoder.finish().and_then(|_| pipeline.finish()) {
+        Ok(out) => out,
+        Err(e) => {
+            handle_failed_message(policy, dead_letters, "", &chunk_context(offset), e)?;
+            Vec::new()
+        }
+    };
+    bytes_out += out.len();
+    writeln!(output, "{}", hex::encode(&out)).map_err(io_err)?;
+    output.flush().map_err(io_err)?;
+
+    info!("[{}] Streamed {} bytes in, {} bytes out", instance_id, bytes_in, bytes_out);
+    Ok(())
+}
+
 pub(crate) fn main_internal(cfg: Config) -> Result<(), Box<dyn Error>> {
     // Generate a unique instance ID for this run
     let instance_id = make_instance_id();
     
     if cfg.log_enabled {
         let level = LevelFilter::from_str(&cfg.log_level).unwrap_or(LevelFilter::Info);
-        let file = OpenOptions::new()
-            .append(cfg.log_append)
-            .create(true)
-            .open(&cfg.log_file)
-            .map_err(|e| ByteProcError::Io(e.to_string()))?;
+        let file = RotatingLogFile::open(
+            Path::new(&cfg.log_file),
+            cfg.log_max_file_size()?,
+            cfg.log_rotation_count,
+            cfg.log_append,
+        )?;
         
         // Configure logger with instance ID in the format
         let log_cfg = ConfigBuilder::new()
@@ -649,158 +6630,227 @@ pub(crate) fn main_internal(cfg: Config) -> Result<(), Box<dyn Error>> {
             .add_filter_ignore_str("mio")  // Ignore noisy libraries
             .set_time_to_local(true)
             .build();
-        // TODO: replace unwrap with error handling 
-        WriteLogger::init(level, log_cfg, file).unwrap();
+        let mut loggers: Vec<Box<dyn SharedLogger>> = Vec::new();
+        if cfg.log_structured {
+            loggers.push(Box::new(JsonLogger { level, file: Mutex::new(file) }));
+        } else {
+            loggers.push(WriteLogger::new(level, log_cfg, file));
+        }
+        // The collector binds the endpoint instances publish to
+        if let Some(endpoint) = &cfg.log_zmq_socket
+            && cfg.command.is_none()
+        {
+            loggers.push(Box::new(ZmqLogger::open(level, endpoint, &cfg)?));
+        }
+        CombinedLogger::init(loggers).map_err(|e| ByteProcError::InvalidConfiguration(e.to_string()))?;
         
         // Log the start of this instance
         info!("[{}] Byteproc starting up", instance_id);
     }
+
+    if let Some(Command::LogCollector(args)) = &cfg.command {
+        info!("[{}] Collecting log records on {}", instance_id, cfg.log_zmq_socket.as_deref().unwrap_or_default());
+        let written = match &args.file {
+            Some(path) => {
+                let mut file = OpenOptions::new().append(true).create(true).open(path)
+                    .map_err(|e| ByteProcError::Io(format!("{}: {}", path.display(), e)))?;
+                collect_logs(&cfg, args, &mut file)?
+            }
+            None => collect_logs(&cfg, args, &mut io::stdout().lock())?,
+        };
+        info!("[{}] Wrote {} log records", instance_id, written);
+        return Ok(());
+    }
     
-    // Prepare ZeroMQ if needed
+    if cfg.invert_pipeline == InvertPipeline::Emit {
+        let inverted = serde_json::to_string_pretty(&cfg.inverted()?)
+            .map_err(|e| ByteProcError::InvalidConfiguration(e.to_string()))?;
+        println!("{}", inverted);
+        info!("[{}] Wrote inverse config", instance_id);
+        return Ok(());
+    }
+
+    // Plugins are loaded once and shared by the main pipeline and the routes
+    let plugins = cfg.plugins()?;
+    let registry = ModuleRegistry::with_plugins(&cfg, &plugins)?;
+    let registry = if cfg.invert_pipeline == InvertPipeline::Run {
+        info!("[{}] Running the inverse pipeline", instance_id);
+        registry.inverse()?
+    } else {
+        registry
+    };
+
     let context = Context::new();
-    let mut input_socket: Option<Socket> = None;
-    let mut output_socket: Option<Socket> = None;
-
-    match cfg.input_type {
-        InputType::ZmqPull => {
-            let sock = context.socket(zmq::PULL)?;
-            sock.set_reconnect_ivl(cfg.zmq_reconnect_interval_ms as i32)?;
-            sock.set_reconnect_ivl_max(cfg.zmq_max_reconnect_attempts as i32)?;
-            sock.set_rcvtimeo(cfg.zmq_receive_timeout_ms)?;
-            sock.set_linger(cfg.zmq_linger_ms)?;
-            
-            if cfg.input_zmq_bind {
-                info!("[{}] Binding PULL socket to {}", instance_id, 
-                    cfg.input_zmq_socket.as_ref().unwrap());
-                sock.bind(cfg.input_zmq_socket.as_ref().unwrap())?;
-            } else {
-                info!("[{}] Connecting PULL socket to {}", instance_id,
-                    cfg.input_zmq_socket.as_ref().unwrap());
-                sock.connect(cfg.input_zmq_socket.as_ref().unwrap())?;
-            }
-            input_socket = Some(sock);
-        },
-        InputType::Stdin => {
-            // No socket setup needed for stdin
+    let mut dead_letters = open_dead_letters(&context, &cfg)?;
+
+    if cfg.stream {
+        info!("[{}] Streaming stdin to stdout in {} KB chunks", instance_id, cfg.stream_chunk_kb);
+        stream_hex_with_policy(
+            &registry,
+            cfg.max_stream_size()?,
+            cfg.stream_chunk_kb * 1024,
+            io::stdin().lock(),
+            io::stdout().lock(),
+            cfg.error_policy,
+            &mut dead_letters,
+        )?;
+        if dead_letters.has_zmq() {
+            // Same delay as below, for dead letters sent over ZMQ
+            std::thread::sleep(std::time::Duration::from_millis(100));
         }
+        info!("[{}] Processing complete", instance_id);
+        return Ok(());
     }
-    
-    match cfg.output_type {
-        OutputType::ZmqPush => {
-            let sock = context.socket(zmq::PUSH)?;
-            sock.set_reconnect_ivl(cfg.zmq_reconnect_interval_ms as i32)?;
-            sock.set_reconnect_ivl_max(cfg.zmq_max_reconnect_attempts as i32)?;
-            sock.set_sndtimeo(cfg.zmq_send_timeout_ms)?;
-            sock.set_linger(cfg.zmq_linger_ms)?;
-            
-            if cfg.output_zmq_bind {
-                info!("[{}] Binding PUSH socket to {}", instance_id, 
-                    cfg.output_zmq_socket.as_ref().unwrap());
-                sock.bind(cfg.output_zmq_socket.as_ref().unwrap())?;
-            } else {
-                info!("[{}] Connecting PUSH socket to {}", instance_id,
-                    cfg.output_zmq_socket.as_ref().unwrap());
-                sock.connect(cfg.output_zmq_socket.as_ref().unwrap())?;
-            }
-            output_socket = Some(sock);
-        },
-        OutputType::Stdout => {
-            // No socket setup needed for stdout
-        }
-    }
-
-    // Read input
-    let raw_hex = match cfg.input_type {
-        InputType::Stdin => {
-            let mut s = String::new();
-            info!("[{}] Reading from stdin...", instance_id);
-            io::stdin().read_to_string(&mut s)
-                .map_err(|e| ByteProcError::Io(e.to_string()))?;
-            info!("[{}] Finished reading from stdin ({} chars)", instance_id, s.trim().len());
-            s.trim().to_string()
-        },
-        InputType::ZmqPull => {
-            // This is the zmq_pull case
-            let socket_ref = input_socket
-                .as_ref()
-                .ok_or_else(|| ByteProcError::InvalidConfiguration("Input socket not initialized for ZMQ".into()))?;
 
+    // Open every output before reading input
+    let router = Router::new(&cfg, &plugins)?;
+    let mut fan_out = FanOut::open(&context, &cfg.output_specs(), &cfg)?;
+    let inputs = cfg.input_specs();
+
+    if inputs.is_empty() {
+        let mut s = String::new();
+        info!("[{}] Reading from stdin...", instance_id);
+        io::stdin().read_to_string(&mut s)
+            .map_err(|e| ByteProcError::Io(e.to_string()))?;
+        info!("[{}] Finished reading from stdin ({} chars)", instance_id, s.trim().len());
+        let mut ctx = ProcessContext::new(message_sequence(0), "stdin");
+        if let Err(MessageError { input, error }) = process_message(&cfg, &registry, &router, s.trim(), &mut ctx, &mut fan_out) {
+            handle_failed_message(cfg.error_policy, &mut dead_letters, s.trim(), &ctx, error)
+                .map_err(|error| MessageError { input, error })?;
+        }
+        set_log_message(None);
+    } else {
+        let mut fan_in = FanIn::open(&context, &inputs, &cfg)?;
+        let mut received: u64 = 0;
+        while cfg.message_count == 0 || received < cfg.message_count {
             info!(
-                "[{}] Waiting for ZMQ message on PULL socket (timeout: {}ms)...",
-                instance_id, cfg.zmq_receive_timeout_ms
+                "[{}] Waiting for ZMQ message on {} PULL socket(s) (timeout: {}ms)...",
+                instance_id, inputs.len(), cfg.zmq_receive_timeout_ms
             );
-            let msg = socket_ref
-                .recv_msg(0)
-                .map_err(|e| {
-                    error!("[{}] ZMQ recv_msg error: {}", instance_id, e);
-                    ByteProcError::Zmq(e.to_string())
-                })?;
-            info!("[{}] Received ZMQ message ({} bytes)", instance_id, msg.len());
-
-            let s = msg.as_str()
-                .ok_or_else(|| {
-                    error!("[{}] Failed to convert ZMQ message to UTF-8 string", instance_id);
-                    ByteProcError::HexDecode("Invalid UTF-8 from ZMQ".into())
-                })?;
-            info!("[{}] Successfully converted ZMQ message to string ({} chars)", 
-                instance_id, s.trim().len());
-            s.trim().to_string()
+            let Some(msg) = fan_in.recv(cfg.zmq_receive_timeout_ms as i64)? else {
+                if cfg.message_count == 0 && received > 0 {
+                    info!("[{}] No message within the receive timeout, stopping", instance_id);
+                    break;
+                }
+                error!("[{}] No ZMQ message within {}ms", instance_id, cfg.zmq_receive_timeout_ms);
+                return Err(ByteProcError::Zmq("Resource temporarily unavailable".into()).into());
+            };
+            let mut ctx = match msg.context {
+                Some(upstream) => ProcessContext::received(upstream, &msg.endpoint),
+                None => ProcessContext::new(message_sequence(received), &msg.endpoint),
+            };
+            if let Err(MessageError { input, error }) = process_message(&cfg, &registry, &router, &msg.hex, &mut ctx, &mut fan_out) {
+                handle_failed_message(cfg.error_policy, &mut dead_letters, &msg.hex, &ctx, error)
+                    .map_err(|error| MessageError { input, error })?;
+            }
+            set_log_message(None);
+            received += 1;
         }
-    };
-    info!("[{}] Received hex input (len={} chars)", instance_id, raw_hex.len());
+    }
+
+    if fan_out.has_zmq() || dead_letters.has_zmq() {
+        // Add a small delay to allow ZMQ to send the message before the socket is closed/dropped.
+        // This is a MUST until a better solution is found, otherwise the packet is never sent. 100 msec always has worked.
+        // TODO: find a better solution that is platform independent 
+        std::thread::sleep(std::time::Duration::from_millis(100)); 
+        info!("[{}] ZMQ send initiated and a short delay completed.", instance_id);
+    }
+
+    info!("[{}] Processing complete", instance_id);
+
+    Ok(())
+}
+
+/// Decode, process and deliver one hex message. A failure carries a
+/// summary of the message for the error report.
+fn process_message(
+    cfg: &Config,
+    registry: &ModuleRegistry,
+    router: &Router,
+    raw_hex: &str,
+    ctx: &mut ProcessContext,
+    fan_out: &mut FanOut,
+) -> Result<(), MessageError> {
+    set_log_message(Some(ctx.sequence));
+    deliver_message(cfg, registry, router, raw_hex, ctx, fan_out)
+        .map_err(|error| MessageError { input: InputSummary::new(raw_hex, ctx), error })
+}
+
+fn deliver_message(
+    cfg: &Config,
+    registry: &ModuleRegistry,
+    router: &Router,
+    raw_hex: &str,
+    ctx: &mut ProcessContext,
+    fan_out: &mut FanOut,
+) -> Result<(), ByteProcError> {
+    let instance_id = make_instance_id();
+    info!(hex_chars = raw_hex.len(), source = ctx.source.as_str(); "[{}] Received hex input (len={} chars)", instance_id, raw_hex.len());
 
     // Decode hex
-    let bytes = Vec::from_hex(&raw_hex)
+    let bytes = Vec::from_hex(raw_hex)
         .map_err(|e| ByteProcError::HexDecode(e.to_string()))?;
     if bytes.len() > cfg.max_stream_size()? {
-        return Err(ByteProcError::MaxSizeExceeded(cfg.max_stream_size()?, bytes.len()).into());
+        return Err(ByteProcError::MaxSizeExceeded(cfg.max_stream_size()?, bytes.len()));
     }
 
     // Process modules
-    let registry = ModuleRegistry::new(&cfg)?;
-    let processed = registry.process_all(bytes)?;
+    let (outcome, outputs) = match registry.process_all_with_context(bytes, ctx)? {
+        PipelineOutcome::Output(processed) => router.dispatch(processed, ctx)?,
+        dropped => (dropped, &[][..]),
+    };
+    info!("[{}] Message context: {}", instance_id, ctx.to_json());
+    let processed = match outcome {
+        PipelineOutcome::Output(processed) => processed,
+        PipelineOutcome::Dropped { stage } => {
+            info!("[{}] Message dropped by {}", instance_id, stage);
+            return Ok(());
+        }
+    };
 
     if processed.len() > cfg.max_stream_size()? {
-        return Err(ByteProcError::MaxSizeExceeded(cfg.max_stream_size()?, processed.len()).into());
+        return Err(ByteProcError::MaxSizeExceeded(cfg.max_stream_size()?, processed.len()));
     }
 
-    // Encode hex
+    // Encode hex and write to every output
     let out_hex = hex::encode(&processed);
-
-    // Write output
-    match cfg.output_type {
-        OutputType::Stdout => {
-            info!("[{}] Writing output to stdout", instance_id);
-            println!("{}", out_hex);
-        },
-        OutputType::ZmqPush => {
-            info!("[{}] Sending output via ZMQ", instance_id);
-            let socket = output_socket
-                .as_ref()
-                .ok_or_else(|| ByteProcError::InvalidConfiguration("Output socket not initialized for ZMQ PUSH".into()))?;
-            
-            socket.send(&out_hex, 0)
-                .map_err(|e| ByteProcError::Zmq(e.to_string()))?;
-            
-            // Add a small delay to allow ZMQ to send the message before the socket is closed/dropped.
-            // This is a MUST until a better solution is found, otherwise the packet is never sent. 100 msec always has worked.
-            // TODO: find a better solution that is platform independent 
-            std::thread::sleep(std::time::Duration::from_millis(100)); 
-            info!("[{}] ZMQ send initiated and a short delay completed.", instance_id);
-        }
-    }
-
-    info!("[{}] Processing complete", instance_id);
-
+    fan_out.send_to(&out_hex, ctx, outputs)?;
+    info!(bytes_out = processed.len(), outputs = outputs.len(); "[{}] Message {} delivered", instance_id, ctx.sequence);
     Ok(())
 }
 
 
 }
 
+static ERROR_FORMAT: std::sync::OnceLock<crate::processor::ErrorFormat> = std::sync::OnceLock::new();
+
 /// A convenient entrypoint for the binary:
 pub fn run_app() -> Result<(), Box<dyn std::error::Error>> {
-    let cfg = crate::processor::Config::load()?;
-    crate::processor::main_internal(cfg)?;
-    Ok(())
+    use clap::Parser;
+    let cfg = match crate::processor::Config::load() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            // The config file may be what failed, so only the CLI picks the format
+            let format = crate::processor::Config::try_parse().map(|c| c.error_format).unwrap_or_default();
+            let _ = ERROR_FORMAT.set(format);
+            return Err(e.into());
+        }
+    };
+    let _ = ERROR_FORMAT.set(cfg.error_format);
+    let result = crate::processor::main_internal(cfg);
+    // Let buffered and published log records out before the process exits
+    log::logger().flush();
+    result
+}
+
+/// Print the error of a failed `run_app` on stderr in the chosen `--error-format`
+/// and return the process exit code for it
+pub fn report_error(err: &(dyn std::error::Error + 'static)) -> i32 {
+    use crate::processor::{ErrorFormat, ErrorReport};
+    let report = ErrorReport::from_error(err);
+    match ERROR_FORMAT.get().copied().unwrap_or_default() {
+        ErrorFormat::Text => eprintln!("Error: {}", err),
+        ErrorFormat::Json => eprintln!("{}", report.to_json()),
+    }
+    report.exit_code
 }
\ No newline at end of file
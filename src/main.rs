@@ -1,10 +1,11 @@
 // src/main.rs
 
-use byteproc::run_app;
+use byteproc::{report_error, run_app};
 
 fn main() {
     if let Err(e) = run_app() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+        // Distinct codes per error kind, see ByteProcError::exit_code
+        let code = report_error(e.as_ref());
+        std::process::exit(code);
     }
 }
@@ -2,9 +2,26 @@ use byteproc::processor::{Passthrough, ByteProcessor};
 use byteproc::processor::{Config, ModuleRegistry}; // Removed ByteProcError as it's unused
 use clap::Parser; // Import the Parser trait
 use hex;
+use std::ffi::c_void;
+use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 use byteproc::processor::{
     InputType, OutputType, Base64Mode, Base64Module, XorModule,
+    LineCodeModule, LineCodeScheme, BitsModule, BitOrder,
+    FrameFormat, FramerModule, DeframerModule, FrameCrc, FrameSearch, Endianness,
+    ChannelModule, ChannelParams, ChannelModel,
+    SliceModule, PadModule, PadScheme, PrependModule, AppendModule, ReverseModule, SwapModule,
+    BitReverseModule, BitopsModule, BitOp, BitScope,
+    SboxModule, SboxMode, RleModule, HuffmanModule, RepetitionModule, RepetitionMode, RepetitionUnit, SymbolsModule,
+    ScriptModule, ScriptLimits, load_plugins, WasmModule, WasmLimits, StreamProcessor, stream_hex, stream_hex_with_policy,
+    ProcessContext, Sink, FileSink, FanOut, FanIn, InputSpec, SinkErrorPolicy,
+    Router, Predicate, InvertPipeline, PadMode,
+    ErrorPolicy, DeadLetter, open_dead_letters, handle_failed_message, StageErrorPolicy, StageSpec, PipelineOutcome, RleMode,
+    ErrorReport, InputSummary, MessageError, Command, LogCollectorArgs,
+    LogRecord, iso8601_utc, RotatingLogFile,
+    PluginBuffer, PluginDescriptor, PLUGIN_ABI_VERSION, read_descriptor,
     ByteProcError,
 };
 
@@ -90,6 +107,7 @@ fn test_output_type_from_str_and_display() {
     assert_eq!(OutputType::from_str("zmq_push").unwrap(), OutputType::ZmqPush);
     assert_eq!(format!("{}", OutputType::Stdout), "stdout");
     assert_eq!(format!("{}", OutputType::ZmqPush), "zmq_push");
+    assert_eq!(OutputType::from_str("file").unwrap(), OutputType::File);
     assert!(OutputType::from_str("invalid").is_err());
 }
 
@@ -174,11 +192,14 @@ fn test_base64_module_roundtrip_and_error() {
     let dec = Base64Module::new(false, true).process(&enc).unwrap();
     assert_eq!(&dec, plaintext);
 
-    // decode invalid input → Module error
+    // decode invalid input → InvalidInput error at the bad character
     let err = Base64Module::new(false, true)
         .process(b"!!! not base64 !!!")
         .unwrap_err();
-    assert!(matches!(err, ByteProcError::Module(_)));
+    assert!(matches!(err, ByteProcError::InvalidInput { offset: 0, .. }));
+    let err = Base64Module::new(false, true).process(b"QUJD!A==").unwrap_err();
+    assert!(matches!(err, ByteProcError::InvalidInput { offset: 4, .. }));
+    assert_eq!(err.exit_code(), 65);
 }
 
 #[test]
@@ -220,3 +241,1476 @@ fn test_module_registry_xor_then_base64() {
     let out = registry.process_all(vec![0xff]).unwrap();
     assert_eq!(out, b"AA".to_vec());
 }
+
+#[test]
+fn test_linecode_manchester_conventions() {
+    // 0xa0 = 1010 0000
+    let ieee = LineCodeModule::new(LineCodeScheme::ManchesterIeee, true, false);
+    assert_eq!(ieee.process(&[0xa0]).unwrap(), vec![0x66, 0xaa]);
+    let thomas = LineCodeModule::new(LineCodeScheme::ManchesterThomas, true, false);
+    assert_eq!(thomas.process(&[0xa0]).unwrap(), vec![0x99, 0x55]);
+
+    // unpacked output is one chip per byte
+    let unpacked = LineCodeModule::new(LineCodeScheme::ManchesterIeee, true, true);
+    assert_eq!(unpacked.process(&[0x80]).unwrap(), vec![0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0]);
+}
+
+#[test]
+fn test_linecode_roundtrip_all_schemes() {
+    let data = vec![0x00, 0xff, 0x5a, 0x3c, 0x81];
+    for scheme in [
+        LineCodeScheme::ManchesterIeee,
+        LineCodeScheme::ManchesterThomas,
+        LineCodeScheme::DiffManchester,
+        LineCodeScheme::Nrzi,
+        LineCodeScheme::Differential,
+    ] {
+        for unpacked in [false, true] {
+            let enc = LineCodeModule::new(scheme, true, unpacked).process(&data).unwrap();
+            let dec = LineCodeModule::new(scheme, false, unpacked).process(&enc).unwrap();
+            assert_eq!(dec, data, "roundtrip failed for {} (unpacked={})", scheme, unpacked);
+        }
+    }
+}
+
+#[test]
+fn test_linecode_decode_errors() {
+    // odd number of chips cannot be Manchester decoded
+    let dec = LineCodeModule::new(LineCodeScheme::ManchesterIeee, false, true);
+    assert!(matches!(dec.process(&[0, 1, 1]), Err(ByteProcError::InvalidInput { offset: 2, .. })));
+    // 00 is not a valid Manchester symbol
+    assert!(matches!(dec.process(&[0, 0]), Err(ByteProcError::InvalidInput { offset: 0, .. })));
+    assert!(matches!(dec.process(&[0, 1, 0, 0]), Err(ByteProcError::InvalidInput { offset: 2, .. })));
+    let packed = LineCodeModule::new(LineCodeScheme::ManchesterIeee, false, false);
+    assert!(matches!(packed.process(&[0x55, 0x50]), Err(ByteProcError::InvalidInput { offset: 1, .. })));
+    // unpacked input must be 0 or 1
+    assert!(matches!(dec.process(&[0, 2]), Err(ByteProcError::InvalidInput { offset: 1, .. })));
+    // odd bit counts are fine for NRZI, the last byte is zero padded
+    let nrzi = LineCodeModule::new(LineCodeScheme::Nrzi, false, true);
+    assert_eq!(nrzi.process(&[0, 0, 1]).unwrap(), vec![0xc0]);
+}
+
+#[test]
+fn test_bits_unpack_and_pack() {
+    // one bit per byte, MSB first
+    let unpack = BitsModule::new(true, 1, BitOrder::Msb).unwrap();
+    assert_eq!(unpack.process(&[0xa5]).unwrap(), vec![1, 0, 1, 0, 0, 1, 0, 1]);
+    // LSB first
+    let unpack_lsb = BitsModule::new(true, 1, BitOrder::Lsb).unwrap();
+    assert_eq!(unpack_lsb.process(&[0xa5]).unwrap(), vec![1, 0, 1, 0, 0, 1, 0, 1].into_iter().rev().collect::<Vec<u8>>());
+    // two bits per byte for QPSK style symbol mapping
+    let unpack2 = BitsModule::new(true, 2, BitOrder::Msb).unwrap();
+    assert_eq!(unpack2.process(&[0xe4]).unwrap(), vec![3, 2, 1, 0]);
+
+    // pack reverses unpack for every k and order
+    let data = vec![0x12, 0x34, 0xfe, 0x01, 0x80, 0x7f];
+    for k in 1..=8u8 {
+        for order in [BitOrder::Msb, BitOrder::Lsb] {
+            let unpacked = BitsModule::new(true, k, order).unwrap().process(&data).unwrap();
+            let packed = BitsModule::new(false, k, order).unwrap().process(&unpacked).unwrap();
+            // 48 bits split into k-bit chunks may leave a zero padded tail
+            assert_eq!(&packed[..data.len()], &data[..], "k={} order={}", k, order);
+        }
+    }
+}
+
+#[test]
+fn test_bits_invalid_input_and_config() {
+    assert!(matches!(BitsModule::new(true, 0, BitOrder::Msb), Err(ByteProcError::InvalidConfiguration(_))));
+    assert!(matches!(BitsModule::new(true, 9, BitOrder::Msb), Err(ByteProcError::InvalidConfiguration(_))));
+    // 0x04 does not fit in two bits
+    let pack2 = BitsModule::new(false, 2, BitOrder::Msb).unwrap();
+    assert!(matches!(pack2.process(&[0x01, 0x04]), Err(ByteProcError::InvalidInput { offset: 1, .. })));
+}
+
+fn frame_format(crc: FrameCrc) -> FrameFormat {
+    FrameFormat::new("aaaa", "1acffc1d", 2, Endianness::Big, crc).unwrap()
+}
+
+#[test]
+fn test_framer_layout() {
+    let framer = FramerModule::new(frame_format(FrameCrc::None));
+    let out = framer.process(&[0xde, 0xad]).unwrap();
+    assert_eq!(hex::encode(out), "aaaa1acffc1d0002dead");
+
+    // little endian length field
+    let le = FrameFormat::new("", "7e", 4, Endianness::Little, FrameCrc::None).unwrap();
+    let out = FramerModule::new(le).process(&[0x01]).unwrap();
+    assert_eq!(hex::encode(out), "7e0100000001");
+
+    // CRC-32 of length||payload ("123456789" check value is cbf43926)
+    let crc = FrameFormat::new("", "7e", 1, Endianness::Big, FrameCrc::Crc32).unwrap();
+    let out = FramerModule::new(crc).process(b"").unwrap();
+    // CRC-32 of the single length byte 0x00
+    assert_eq!(hex::encode(out), "7e00d202ef8d");
+}
+
+#[test]
+fn test_deframer_roundtrip_multiple_frames() {
+    for crc in [FrameCrc::None, FrameCrc::Crc16, FrameCrc::Crc32] {
+        let framer = FramerModule::new(frame_format(crc));
+        let mut stream = vec![0x00, 0x13, 0x37];
+        stream.extend(framer.process(b"first").unwrap());
+        stream.extend([0xff, 0xee]);
+        stream.extend(framer.process(b"second").unwrap());
+
+        let deframer = DeframerModule::new(frame_format(crc), 0, FrameSearch::Byte).unwrap();
+        assert_eq!(deframer.process(&stream).unwrap(), b"firstsecond".to_vec());
+    }
+}
+
+#[test]
+fn test_deframer_bit_search_with_sync_errors() {
+    let framer = FramerModule::new(frame_format(FrameCrc::Crc16));
+    let mut frame = framer.process(b"hi").unwrap();
+    // flip two bits in the sync word
+    frame[2] ^= 0x81;
+
+    // shift the whole frame by 3 bits so it is no longer byte aligned
+    let bits = BitsModule::new(true, 1, BitOrder::Msb).unwrap().process(&frame).unwrap();
+    let mut shifted = vec![1, 0, 1];
+    shifted.extend(bits);
+    let stream = BitsModule::new(false, 1, BitOrder::Msb).unwrap().process(&shifted).unwrap();
+
+    let strict = DeframerModule::new(frame_format(FrameCrc::Crc16), 1, FrameSearch::Bit).unwrap();
+    assert!(strict.process(&stream).unwrap().is_empty());
+    let tolerant = DeframerModule::new(frame_format(FrameCrc::Crc16), 2, FrameSearch::Bit).unwrap();
+    assert_eq!(tolerant.process(&stream).unwrap(), b"hi".to_vec());
+    // byte aligned search cannot find it
+    let aligned = DeframerModule::new(frame_format(FrameCrc::Crc16), 2, FrameSearch::Byte).unwrap();
+    assert!(aligned.process(&stream).unwrap().is_empty());
+}
+
+#[test]
+fn test_deframer_rejects_bad_crc_and_config() {
+    let framer = FramerModule::new(frame_format(FrameCrc::Crc32));
+    let mut frame = framer.process(b"payload").unwrap();
+    let last = frame.len() - 5;
+    frame[last] ^= 0x01;
+    let deframer = DeframerModule::new(frame_format(FrameCrc::Crc32), 0, FrameSearch::Byte).unwrap();
+    assert!(deframer.process(&frame).unwrap().is_empty());
+
+    // as many tolerated errors as sync bits would match anything
+    assert!(DeframerModule::new(frame_format(FrameCrc::None), 32, FrameSearch::Byte).is_err());
+    assert!(FrameFormat::new("", "7e", 3, Endianness::Big, FrameCrc::None).is_err());
+    assert!(FrameFormat::new("", "", 2, Endianness::Big, FrameCrc::None).is_err());
+}
+
+fn channel_params() -> ChannelParams {
+    ChannelParams {
+        model: ChannelModel::Bsc,
+        seed: 42,
+        ber: 0.0,
+        p_good_to_bad: 0.0,
+        p_bad_to_good: 0.1,
+        ber_bad: 0.5,
+        erasure_rate: 0.0,
+        erasure_byte: 0x00,
+        truncate_bytes: 0,
+    }
+}
+
+#[test]
+fn test_channel_is_reproducible() {
+    let data = vec![0u8; 256];
+    let params = ChannelParams { ber: 0.05, ..channel_params() };
+    let a = ChannelModule::new(params.clone()).unwrap().process(&data).unwrap();
+    let b = ChannelModule::new(params.clone()).unwrap().process(&data).unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, data);
+    let other_seed = ChannelModule::new(ChannelParams { seed: 43, ..params }).unwrap().process(&data).unwrap();
+    assert_ne!(a, other_seed);
+
+    // a clean channel leaves the data alone
+    let clean = ChannelModule::new(channel_params()).unwrap();
+    assert_eq!(clean.process(&data).unwrap(), data);
+}
+
+#[test]
+fn test_channel_errors_differ_between_messages() {
+    let data = vec![0u8; 256];
+    let params = ChannelParams { ber: 0.05, ..channel_params() };
+    let run = || {
+        let channel = ChannelModule::new(params.clone()).unwrap();
+        (channel.process(&data).unwrap(), channel.process(&data).unwrap())
+    };
+    let (first, second) = run();
+    assert_ne!(first, second);
+    assert_eq!(run(), (first, second));
+}
+
+#[test]
+fn test_channel_bursts_erasures_and_truncation() {
+    // always bad, always wrong: every bit is flipped
+    let burst = ChannelParams {
+        model: ChannelModel::GilbertElliott,
+        p_good_to_bad: 1.0,
+        p_bad_to_good: 0.0,
+        ber_bad: 1.0,
+        ..channel_params()
+    };
+    assert_eq!(ChannelModule::new(burst).unwrap().process(&[0x0f, 0xaa]).unwrap(), vec![0xf0, 0x55]);
+
+    let erase = ChannelParams { erasure_rate: 1.0, erasure_byte: 0xee, ..channel_params() };
+    assert_eq!(ChannelModule::new(erase).unwrap().process(&[1, 2, 3]).unwrap(), vec![0xee; 3]);
+
+    let truncate = ChannelParams { truncate_bytes: 2, ..channel_params() };
+    assert_eq!(ChannelModule::new(truncate.clone()).unwrap().process(&[1, 2, 3]).unwrap(), vec![1]);
+    assert!(ChannelModule::new(truncate).unwrap().process(&[1]).unwrap().is_empty());
+
+    let invalid = ChannelParams { ber: 1.5, ..channel_params() };
+    assert!(matches!(ChannelModule::new(invalid), Err(ByteProcError::InvalidConfiguration(_))));
+}
+
+#[test]
+fn test_slice_module() {
+    let data = [0, 1, 2, 3, 4, 5];
+    assert_eq!(SliceModule::new(2, Some(3)).process(&data).unwrap(), vec![2, 3, 4]);
+    assert_eq!(SliceModule::new(4, None).process(&data).unwrap(), vec![4, 5]);
+    assert!(SliceModule::new(6, None).process(&data).unwrap().is_empty());
+    assert!(matches!(SliceModule::new(4, Some(3)).process(&data), Err(ByteProcError::InvalidInput { .. })));
+    assert!(matches!(SliceModule::new(7, None).process(&data), Err(ByteProcError::InvalidInput { .. })));
+}
+
+#[test]
+fn test_pad_module_roundtrip_all_schemes() {
+    let expected = [
+        (PadScheme::Pkcs7, "aabbcc0505050505", "aabbccddeeff00110808080808080808"),
+        (PadScheme::Zero, "aabbcc0000000000", "aabbccddeeff0011"),
+        (PadScheme::Iso7816, "aabbcc8000000000", "aabbccddeeff00118000000000000000"),
+    ];
+    for (scheme, short_padded, full_padded) in expected {
+        let pad = PadModule::new(true, scheme, 8).unwrap();
+        let unpad = PadModule::new(false, scheme, 8).unwrap();
+
+        let out = pad.process(&[0xaa, 0xbb, 0xcc]).unwrap();
+        assert_eq!(hex::encode(&out), short_padded, "{}", scheme);
+        assert_eq!(unpad.process(&out).unwrap(), vec![0xaa, 0xbb, 0xcc]);
+
+        // a full block still gets a padding block for pkcs7 and iso7816
+        let block = hex::decode("aabbccddeeff0011").unwrap();
+        let out = pad.process(&block).unwrap();
+        assert_eq!(hex::encode(&out), full_padded, "{}", scheme);
+        assert_eq!(unpad.process(&out).unwrap(), block);
+    }
+}
+
+#[test]
+fn test_pad_module_rejects_malformed_padding() {
+    let pkcs7 = PadModule::new(false, PadScheme::Pkcs7, 4).unwrap();
+    assert!(pkcs7.process(&[1, 2, 3, 0]).is_err());
+    assert!(pkcs7.process(&[1, 2, 1, 2]).is_err());
+    assert!(pkcs7.process(&[1, 2, 3]).is_err());
+    assert!(matches!(pkcs7.process(&[1, 2, 1, 2]), Err(ByteProcError::InvalidInput { offset: 3, .. })));
+    assert!(matches!(pkcs7.process(&[1, 2, 3]), Err(ByteProcError::InvalidInput { offset: 3, .. })));
+    let iso = PadModule::new(false, PadScheme::Iso7816, 4).unwrap();
+    assert!(iso.process(&[1, 2, 0, 0]).is_err());
+    assert!(matches!(iso.process(&[1, 2, 0, 0]), Err(ByteProcError::InvalidInput { offset: 1, .. })));
+    assert!(PadModule::new(true, PadScheme::Pkcs7, 256).is_err());
+    assert!(PadModule::new(true, PadScheme::Zero, 0).is_err());
+}
+
+#[test]
+fn test_prepend_append_reverse_swap_bitreverse() {
+    let data = [0x01, 0x02, 0x03, 0x04];
+    assert_eq!(PrependModule::new("cafe").unwrap().process(&data).unwrap(), vec![0xca, 0xfe, 1, 2, 3, 4]);
+    assert_eq!(AppendModule::new("beef").unwrap().process(&data).unwrap(), vec![1, 2, 3, 4, 0xbe, 0xef]);
+    assert!(PrependModule::new("xyz").is_err());
+
+    assert_eq!(ReverseModule.process(&data).unwrap(), vec![4, 3, 2, 1]);
+    assert_eq!(SwapModule::new(16).unwrap().process(&data).unwrap(), vec![2, 1, 4, 3]);
+    assert_eq!(SwapModule::new(32).unwrap().process(&data).unwrap(), vec![4, 3, 2, 1]);
+    assert!(SwapModule::new(64).unwrap().process(&data).is_err());
+    assert!(matches!(SwapModule::new(32).unwrap().process(&[1, 2, 3, 4, 5]), Err(ByteProcError::InvalidInput { offset: 4, .. })));
+    assert!(SwapModule::new(24).is_err());
+
+    assert_eq!(BitReverseModule.process(&[0x01, 0xf0, 0xa5]).unwrap(), vec![0x80, 0x0f, 0xa5]);
+    // reverse, swap and bitreverse are their own inverse
+    let twice = BitReverseModule.process(&BitReverseModule.process(&data).unwrap()).unwrap();
+    assert_eq!(twice, data);
+}
+
+#[test]
+fn test_bitops_mask_operations() {
+    let and = BitopsModule::new(BitOp::And, Some("0ff0"), 0, BitScope::Byte).unwrap();
+    assert_eq!(and.process(&[0xff, 0xff, 0x3c]).unwrap(), vec![0x0f, 0xf0, 0x0c]);
+    let or = BitopsModule::new(BitOp::Or, Some("01"), 0, BitScope::Byte).unwrap();
+    assert_eq!(or.process(&[0x00, 0x10]).unwrap(), vec![0x01, 0x11]);
+    assert!(and.inverse().is_none());
+    assert!(matches!(
+        BitopsModule::new(BitOp::And, None, 0, BitScope::Byte),
+        Err(ByteProcError::InvalidConfiguration(_))
+    ));
+}
+
+#[test]
+fn test_bitops_shifts_and_rotations() {
+    let data = [0x81, 0x42];
+    let rotl_byte = BitopsModule::new(BitOp::Rotl, None, 1, BitScope::Byte).unwrap();
+    assert_eq!(rotl_byte.process(&data).unwrap(), vec![0x03, 0x84]);
+    let rotl_buf = BitopsModule::new(BitOp::Rotl, None, 1, BitScope::Buffer).unwrap();
+    assert_eq!(rotl_buf.process(&data).unwrap(), vec![0x02, 0x85]);
+    let shr_buf = BitopsModule::new(BitOp::Shr, None, 4, BitScope::Buffer).unwrap();
+    assert_eq!(shr_buf.process(&data).unwrap(), vec![0x08, 0x14]);
+    let shl_byte = BitopsModule::new(BitOp::Shl, None, 9, BitScope::Byte).unwrap();
+    assert_eq!(shl_byte.process(&data).unwrap(), vec![0x00, 0x00]);
+    assert!(shr_buf.inverse().is_none());
+}
+
+#[test]
+fn test_bitops_inverse_roundtrip() {
+    let data = hex::decode("00112233445566778899aabbccddeeff").unwrap();
+    for (op, scope) in [
+        (BitOp::Not, BitScope::Byte),
+        (BitOp::Rotl, BitScope::Byte),
+        (BitOp::Rotr, BitScope::Byte),
+        (BitOp::Rotl, BitScope::Buffer),
+        (BitOp::Rotr, BitScope::Buffer),
+    ] {
+        let forward = BitopsModule::new(op, None, 13, scope).unwrap();
+        let inverse = forward.inverse().expect("operation should be invertible");
+        let out = forward.process(&data).unwrap();
+        assert_ne!(out, data);
+        assert_eq!(inverse.process(&out).unwrap(), data, "{} {}", op, scope);
+    }
+}
+
+/// Table for b -> b * 7 + 3 (mod 256), a permutation since 7 is odd
+fn sbox_table() -> Vec<u8> {
+    (0..=255u8).map(|b| b.wrapping_mul(7).wrapping_add(3)).collect()
+}
+
+#[test]
+fn test_sbox_forward_and_inverse() {
+    let table = sbox_table();
+    let forward = SboxModule::from_hex(&hex::encode(&table), false).unwrap();
+    assert!(forward.is_permutation());
+    assert_eq!(forward.process(&[0, 1, 2]).unwrap(), vec![3, 10, 17]);
+
+    let inverse = SboxModule::new(&table, true).unwrap();
+    let data: Vec<u8> = (0..=255).collect();
+    assert_eq!(inverse.process(&forward.process(&data).unwrap()).unwrap(), data);
+}
+
+#[test]
+fn test_sbox_from_file_and_config() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    // one row of 16 entries per line, like a textbook S-box listing
+    for row in sbox_table().chunks(16) {
+        writeln!(file, "{}", hex::encode(row)).unwrap();
+    }
+
+    let mut cfg = Config::default();
+    cfg.sbox_enabled = true;
+    cfg.sbox_table_file = Some(file.path().to_path_buf());
+    cfg.sbox_mode = SboxMode::Inverse;
+    assert!(cfg.validate().is_ok());
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    assert_eq!(registry.process_all(vec![3, 10, 17]).unwrap(), vec![0, 1, 2]);
+
+    // table and file together are ambiguous
+    cfg.sbox_table = Some(hex::encode(sbox_table()));
+    assert!(cfg.validate().is_err());
+}
+
+#[test]
+fn test_sbox_invalid_tables() {
+    assert!(matches!(SboxModule::new(&[0u8; 255], false), Err(ByteProcError::InvalidConfiguration(_))));
+    // not a permutation: fine forward, rejected for inverse
+    let mut table = sbox_table();
+    table[1] = table[0];
+    let forward = SboxModule::new(&table, false).unwrap();
+    assert!(!forward.is_permutation());
+    assert!(matches!(SboxModule::new(&table, true), Err(ByteProcError::InvalidConfiguration(_))));
+}
+
+#[test]
+fn test_rle_packbits_reference_vector() {
+    // Example from Apple's PackBits technical note
+    let raw = hex::decode("aaaaaa80002aaaaaaaaa80002a22aaaaaaaaaaaaaaaaaaaa").unwrap();
+    let packed = RleModule::new(true).process(&raw).unwrap();
+    assert_eq!(hex::encode(&packed), "feaa0280002afdaa0380002a22f7aa");
+    assert_eq!(RleModule::new(false).process(&packed).unwrap(), raw);
+}
+
+#[test]
+fn test_rle_roundtrip_and_malformed_input() {
+    let mut data = vec![7u8; 300];
+    data.extend((0..=255).collect::<Vec<u8>>());
+    data.extend([1, 1, 2, 2, 3]);
+    let enc = RleModule::new(true).process(&data).unwrap();
+    assert_eq!(RleModule::new(false).process(&enc).unwrap(), data);
+    assert!(RleModule::new(true).process(&[]).unwrap().is_empty());
+
+    let dec = RleModule::new(false);
+    // literal run of 4 with only 2 bytes
+    assert!(matches!(dec.process(&[0x03, 0x01, 0x02]), Err(ByteProcError::InvalidInput { offset: 0, .. })));
+    // repeat run without its byte
+    assert!(matches!(dec.process(&[0xfe]), Err(ByteProcError::InvalidInput { offset: 0, .. })));
+    // 0x80 is a no-op
+    assert_eq!(dec.process(&[0x80, 0x00, 0x41]).unwrap(), vec![0x41]);
+}
+
+#[test]
+fn test_huffman_roundtrip() {
+    let enc = HuffmanModule::new(true);
+    let dec = HuffmanModule::new(false);
+    let text = b"this is an example of a huffman tree, it compresses text well".to_vec();
+    let all: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    for data in [text.clone(), all, vec![], vec![0x42; 100]] {
+        let out = enc.process(&data).unwrap();
+        assert_eq!(dec.process(&out).unwrap(), data);
+    }
+    assert!(enc.process(&text).unwrap().len() < text.len() + 6 + 2 * 20);
+
+    // skewed frequencies give long codes
+    let mut skewed = Vec::new();
+    for i in 0..40u32 {
+        skewed.extend(std::iter::repeat_n(i as u8, 1usize << (i / 2).min(16)));
+    }
+    assert_eq!(dec.process(&enc.process(&skewed).unwrap()).unwrap(), skewed);
+}
+
+#[test]
+fn test_huffman_rejects_malformed_input() {
+    let dec = HuffmanModule::new(false);
+    let valid = HuffmanModule::new(true).process(b"abracadabra").unwrap();
+
+    // truncated header, table and bit stream
+    assert!(dec.process(&valid[..4]).is_err());
+    assert!(dec.process(&valid[..8]).is_err());
+    assert!(dec.process(&valid[..valid.len() - 1]).is_err());
+    // symbol listed twice
+    let err = dec.process(&hex::decode("0000000100024101410100").unwrap()).unwrap_err();
+    assert!(matches!(err, ByteProcError::InvalidInput { offset: 8, .. }));
+    // three codes of length 1 cannot exist
+    let err = dec.process(&hex::decode("000000010003410142014301ff").unwrap()).unwrap_err();
+    assert!(matches!(err, ByteProcError::InvalidInput { offset: 6, .. }));
+    // data but no code table
+    assert!(dec.process(&hex::decode("000000010000").unwrap()).is_err());
+
+    // random garbage must be rejected or decoded, never panic
+    let mut state = 0x1234_5678u32;
+    for _ in 0..500 {
+        let garbage: Vec<u8> = (0..40)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let _ = dec.process(&garbage);
+        let _ = RleModule::new(false).process(&garbage);
+    }
+}
+
+#[test]
+fn test_repetition_encode() {
+    let bit3 = RepetitionModule::new(true, 3, RepetitionUnit::Bit).unwrap();
+    // 1010 0000 -> 111000111000 000000000000
+    assert_eq!(bit3.process(&[0xa0]).unwrap(), vec![0xe3, 0x80, 0x00]);
+    let byte3 = RepetitionModule::new(true, 3, RepetitionUnit::Byte).unwrap();
+    assert_eq!(byte3.process(&[0x01, 0x02]).unwrap(), vec![1, 1, 1, 2, 2, 2]);
+    assert!(matches!(
+        RepetitionModule::new(true, 4, RepetitionUnit::Bit),
+        Err(ByteProcError::InvalidConfiguration(_))
+    ));
+}
+
+#[test]
+fn test_repetition_corrects_channel_errors() {
+    let data = b"repetition code".to_vec();
+    for unit in [RepetitionUnit::Bit, RepetitionUnit::Byte] {
+        let enc = RepetitionModule::new(true, 5, unit).unwrap().process(&data).unwrap();
+        let dec = RepetitionModule::new(false, 5, unit).unwrap();
+        assert_eq!(dec.process(&enc).unwrap(), data);
+
+        // a 2% BER channel is well within what five copies can correct
+        let params = ChannelParams { ber: 0.02, ..channel_params() };
+        let noisy = ChannelModule::new(params).unwrap().process(&enc).unwrap();
+        assert_ne!(noisy, enc);
+        assert_eq!(dec.process(&noisy).unwrap(), data, "{}", unit);
+    }
+    // input must be whole groups
+    let dec = RepetitionModule::new(false, 3, RepetitionUnit::Byte).unwrap();
+    assert!(matches!(dec.process(&[1, 1]), Err(ByteProcError::InvalidInput { offset: 0, .. })));
+}
+
+#[test]
+fn test_symbols_gray_mapping() {
+    // 0x1b = 00 01 10 11 -> Gray 00 01 11 10
+    let map = SymbolsModule::new(true, 2, true, BitOrder::Msb).unwrap();
+    assert_eq!(map.process(&[0x1b]).unwrap(), vec![0, 1, 3, 2]);
+    let plain = SymbolsModule::new(true, 2, false, BitOrder::Msb).unwrap();
+    assert_eq!(plain.process(&[0x1b]).unwrap(), vec![0, 1, 2, 3]);
+
+    // 3-bit symbols 0..7 packed MSB first; neighbouring Gray codes differ in one bit
+    let map3 = SymbolsModule::new(true, 3, true, BitOrder::Msb).unwrap();
+    assert_eq!(map3.process(&[0x05, 0x39, 0x77]).unwrap(), vec![0, 1, 3, 2, 6, 7, 5, 4]);
+    let demap3 = SymbolsModule::new(false, 3, true, BitOrder::Msb).unwrap();
+    assert_eq!(demap3.process(&[0, 1, 3, 2, 6, 7, 5, 4]).unwrap(), vec![0x05, 0x39, 0x77]);
+}
+
+#[test]
+fn test_symbols_roundtrip_and_errors() {
+    let data = b"constellation".to_vec();
+    for k in 1..=8 {
+        for order in [BitOrder::Msb, BitOrder::Lsb] {
+            let map = SymbolsModule::new(true, k, true, order).unwrap();
+            let demap = SymbolsModule::new(false, k, true, order).unwrap();
+            let symbols = map.process(&data).unwrap();
+            assert!(symbols.iter().all(|&s| u16::from(s) < 1 << k));
+            // zero padding of a partial last symbol may add a trailing byte
+            assert_eq!(&demap.process(&symbols).unwrap()[..data.len()], &data[..]);
+        }
+    }
+    let demap = SymbolsModule::new(false, 2, false, BitOrder::Msb).unwrap();
+    assert!(matches!(demap.process(&[4]), Err(ByteProcError::InvalidInput { offset: 0, .. })));
+    assert!(matches!(demap.process(&[1, 4]), Err(ByteProcError::InvalidInput { offset: 1, .. })));
+    assert!(matches!(
+        SymbolsModule::new(true, 0, false, BitOrder::Msb),
+        Err(ByteProcError::InvalidConfiguration(_))
+    ));
+}
+
+fn script_limits() -> ScriptLimits {
+    ScriptLimits { max_operations: 100_000, max_size: 4096, timeout: Duration::from_secs(5) }
+}
+
+#[test]
+fn test_script_module_transforms() {
+    let xor = ScriptModule::new(
+        "fn process(bytes) {\n    for i in 0..bytes.len() { bytes[i] = bytes[i] ^ 0xff; }\n    bytes\n}",
+        "xor.rhai",
+        script_limits(),
+    )
+    .unwrap();
+    assert_eq!(xor.process(&[0x00, 0x0f]).unwrap(), vec![0xff, 0xf0]);
+
+    // arrays of integers are accepted as well as blobs
+    let length = ScriptModule::new("fn process(bytes) { [bytes.len()] }", "len.rhai", script_limits()).unwrap();
+    assert_eq!(length.process(&[1, 2, 3]).unwrap(), vec![3]);
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    write!(file, "fn process(bytes) {{ bytes.pop(); bytes }}").unwrap();
+    let from_file = ScriptModule::from_file(&file.path().to_path_buf(), script_limits()).unwrap();
+    assert_eq!(from_file.process(&[1, 2, 3]).unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn test_script_module_errors_and_limits() {
+    let bad_return = ScriptModule::new("fn process(bytes) { [256] }", "s", script_limits()).unwrap();
+    assert!(matches!(bad_return.process(&[]), Err(ByteProcError::Module(_))));
+
+    let runtime = ScriptModule::new("fn process(bytes) {\n    let x = bytes.len();\n    bytes[x + 10]\n}", "index.rhai", script_limits()).unwrap();
+    match runtime.process(&[]) {
+        Err(ByteProcError::Module(msg)) => assert!(msg.contains("index.rhai line 3"), "{}", msg),
+        other => panic!("expected module error, got {:?}", other),
+    }
+
+    let spin = ScriptModule::new("fn process(bytes) { loop {} }", "s", script_limits()).unwrap();
+    assert!(matches!(spin.process(&[]), Err(ByteProcError::Module(_))));
+
+    let grow = ScriptModule::new("fn process(bytes) { bytes.pad(8192, 0); bytes }", "s", script_limits()).unwrap();
+    assert!(matches!(grow.process(&[]), Err(ByteProcError::Module(_))));
+
+    let timed = ScriptLimits { max_operations: 0, timeout: Duration::from_millis(50), ..script_limits() };
+    let slow = ScriptModule::new("fn process(bytes) { loop {} }", "s", timed).unwrap();
+    match slow.process(&[]) {
+        Err(ByteProcError::Module(msg)) => assert!(msg.contains("time limit"), "{}", msg),
+        other => panic!("expected module error, got {:?}", other),
+    }
+
+    assert!(matches!(
+        ScriptModule::new("fn transform(bytes) { bytes }", "s", script_limits()),
+        Err(ByteProcError::Module(_))
+    ));
+    match ScriptModule::new("fn process(bytes) {\n    bytes", "s", script_limits()) {
+        Err(ByteProcError::Module(msg)) => assert!(msg.contains("s line 2"), "{}", msg),
+        other => panic!("expected module error, got {:?}", other.err()),
+    }
+
+    // Scripts cannot import other script files
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("helper.rhai"), "fn id(b) { b }").unwrap();
+    let source = format!(
+        "import \"{}\" as helper;\nfn process(bytes) {{ bytes }}",
+        dir.path().join("helper").display()
+    );
+    let importing = ScriptModule::new(&source, "s", script_limits()).unwrap();
+    assert!(matches!(importing.process(&[1]), Err(ByteProcError::Module(_))));
+}
+
+/// Copy the example plugin built by cargo into a fresh plugin directory
+fn example_plugin_dir() -> tempfile::TempDir {
+    let name = format!(
+        "{}invert_plugin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    // target/<profile>/deps/<test binary> -> target/<profile>/examples
+    let exe = std::env::current_exe().unwrap();
+    let built = exe.parent().unwrap().parent().unwrap().join("examples").join(&name);
+    assert!(built.exists(), "{} not found, build it with cargo build --examples", built.display());
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy(&built, dir.path().join(&name)).unwrap();
+    dir
+}
+
+#[test]
+fn test_pipeline_order_and_unknown_module() {
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["reverse".into(), "prepend".into()];
+    cfg.prepend_hex = "ff".into();
+    assert_eq!(cfg.stage_names(), vec!["reverse", "prepend"]);
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    assert_eq!(registry.process_all(vec![1, 2]).unwrap(), vec![0xff, 2, 1]);
+
+    cfg.pipeline = vec!["prepend".into(), "reverse".into()];
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    assert_eq!(registry.process_all(vec![1, 2]).unwrap(), vec![2, 1, 0xff]);
+
+    cfg.pipeline = vec!["nosuchmodule".into()];
+    assert!(matches!(ModuleRegistry::new(&cfg), Err(ByteProcError::InvalidConfiguration(_))));
+    match cfg.validate() {
+        Err(ByteProcError::InvalidConfiguration(msg)) => assert!(msg.contains("unknown module nosuchmodule"), "{}", msg),
+        other => panic!("expected a config error, got {:?}", other),
+    }
+    // Plugin names are only known once the plugins are loaded
+    cfg.plugin_dir = Some(PathBuf::from("plugins"));
+    assert!(cfg.validate().is_ok());
+    assert!(cfg.validate_stages(&[]).is_err());
+}
+
+#[test]
+fn test_plugin_loaded_by_name() {
+    let dir = example_plugin_dir();
+    let plugins = load_plugins(dir.path()).unwrap();
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].name(), "invert");
+    assert!(plugins[0].config_schema().is_some());
+
+    let mut cfg = Config::default();
+    cfg.plugin_dir = Some(dir.path().to_path_buf());
+    cfg.pipeline = vec!["invert".into(), "reverse".into()];
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    assert_eq!(registry.process_all(vec![0x00, 0x0f]).unwrap(), vec![0xf0, 0xff]);
+
+    cfg.plugin_config.insert("invert".into(), serde_json::json!({ "mask": 1 }));
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    assert_eq!(registry.process_all(vec![0x00, 0x0f]).unwrap(), vec![0x0e, 0x01]);
+
+    // rejected by the schema, then by the plugin itself
+    cfg.plugin_config.insert("invert".into(), serde_json::json!({ "mask": "ff" }));
+    assert!(matches!(ModuleRegistry::new(&cfg), Err(ByteProcError::InvalidConfiguration(_))));
+    cfg.plugin_config.insert("invert".into(), serde_json::json!({ "mask": 256 }));
+    match ModuleRegistry::new(&cfg) {
+        Err(ByteProcError::InvalidConfiguration(msg)) => assert!(msg.contains("0-255"), "{}", msg),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("expected a config error"),
+    }
+}
+
+unsafe extern "C" fn stub_create(_: *const u8, _: usize, _: *mut PluginBuffer) -> *mut c_void {
+    std::ptr::null_mut()
+}
+unsafe extern "C" fn stub_process(_: *mut c_void, _: *const u8, _: usize, _: *mut PluginBuffer) -> i32 {
+    1
+}
+unsafe extern "C" fn stub_free_buffer(_: PluginBuffer) {}
+unsafe extern "C" fn stub_destroy(_: *mut c_void) {}
+
+#[test]
+fn test_plugin_abi_version_mismatch() {
+    let mut descriptor = PluginDescriptor {
+        abi_version: PLUGIN_ABI_VERSION + 1,
+        name: c"stub".as_ptr(),
+        config_schema: std::ptr::null(),
+        create: stub_create,
+        process: stub_process,
+        free_buffer: stub_free_buffer,
+        destroy: stub_destroy,
+    };
+    match unsafe { read_descriptor(&descriptor) } {
+        Err(msg) => assert!(msg.contains(&format!("built for plugin ABI version {}", PLUGIN_ABI_VERSION + 1)), "{}", msg),
+        Ok(_) => panic!("expected an ABI version error"),
+    }
+    descriptor.abi_version = PLUGIN_ABI_VERSION;
+    assert_eq!(unsafe { read_descriptor(&descriptor) }.unwrap(), ("stub".to_string(), None));
+}
+
+const WASM_INVERT: &str = r#"(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) i32.const 1024)
+  (func (export "process") (param $p i32) (param $n i32) (result i64)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (i32.store8
+          (i32.add (local.get $p) (local.get $i))
+          (i32.xor (i32.load8_u (i32.add (local.get $p) (local.get $i))) (i32.const 255)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $p)) (i64.const 32))
+      (i64.extend_i32_u (local.get $n)))))"#;
+
+fn wasm_limits() -> WasmLimits {
+    WasmLimits { fuel: 100_000, max_memory: 1 << 20 }
+}
+
+#[test]
+fn test_wasm_module_transform() {
+    let wasm = wat::parse_str(WASM_INVERT).unwrap();
+    let module = WasmModule::new(&wasm, "invert.wasm", wasm_limits()).unwrap();
+    assert_eq!(module.process(&[0x00, 0x0f, 0xff]).unwrap(), vec![0xff, 0xf0, 0x00]);
+    // each message runs in a fresh instance
+    assert_eq!(module.process(&[0x01]).unwrap(), vec![0xfe]);
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&wasm).unwrap();
+    let from_file = WasmModule::from_file(&file.path().to_path_buf(), wasm_limits()).unwrap();
+    assert_eq!(from_file.process(b"").unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn test_wasm_module_sandbox_limits() {
+    let module = |src: &str, limits: WasmLimits| WasmModule::new(&wat::parse_str(src).unwrap(), "t.wasm", limits);
+
+    // 64 KB pages: a module wanting 32 pages does not fit in 1 MB
+    let big = module(r#"(module (memory (export "memory") 32))"#, wasm_limits());
+    assert!(matches!(big, Err(ByteProcError::InvalidConfiguration(_))));
+
+    let imports = module(
+        r#"(module (import "env" "log" (func (param i32))) (memory (export "memory") 1))"#,
+        wasm_limits(),
+    );
+    assert!(matches!(imports, Err(ByteProcError::InvalidConfiguration(_))));
+
+    let spin = module(
+        r#"(module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) i32.const 0)
+          (func (export "process") (param i32 i32) (result i64) (loop $l (br $l)) i64.const 0))"#,
+        wasm_limits(),
+    )
+    .unwrap();
+    match spin.process(&[1]) {
+        Err(ByteProcError::Module(msg)) => assert!(msg.contains("fuel"), "{}", msg),
+        other => panic!("expected module error, got {:?}", other),
+    }
+
+    let failing = module(
+        r#"(module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) i32.const 0)
+          (func (export "process") (param i32 i32) (result i64) i64.const -1))"#,
+        wasm_limits(),
+    )
+    .unwrap();
+    assert!(matches!(failing.process(&[1]), Err(ByteProcError::Module(_))));
+
+    let out_of_bounds = module(
+        r#"(module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) i32.const 0)
+          (func (export "process") (param i32 i32) (result i64) i64.const 0x0000ffff00000010))"#,
+        wasm_limits(),
+    )
+    .unwrap();
+    assert!(matches!(out_of_bounds.process(&[1]), Err(ByteProcError::Module(_))));
+}
+
+/// Feed `input` to a stream processor in `chunk`-byte pieces
+fn run_stream(stream: &mut dyn StreamProcessor, input: &[u8], chunk: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for piece in input.chunks(chunk) {
+        out.extend(stream.update(piece).unwrap());
+    }
+    out.extend(stream.finish().unwrap());
+    out
+}
+
+#[test]
+fn test_streaming_adapters_match_whole_buffer() {
+    let data: Vec<u8> = (0..2000u32).map(|i| if i % 300 < 150 { 0x55 } else { (i * 7) as u8 }).collect();
+    let modules: Vec<Box<dyn ByteProcessor>> = vec![
+        Box::new(XorModule::new("0123456789", None).unwrap()),
+        Box::new(Base64Module::new(true, true)),
+        Box::new(Base64Module::new(true, false)),
+        Box::new(RleModule::new(true)),
+        Box::new(BitReverseModule),
+    ];
+    for module in &modules {
+        let whole = module.process(&data).unwrap();
+        for chunk in [1, 2, 5, 64, 1000] {
+            let mut stream = module.stream().expect("module streams");
+            assert_eq!(run_stream(stream.as_mut(), &data, chunk), whole, "{} chunk {}", module.name(), chunk);
+        }
+    }
+
+    // decoders carry partial groups and packets across chunk boundaries
+    let decoders: Vec<(Box<dyn ByteProcessor>, Vec<u8>)> = vec![
+        (Box::new(Base64Module::new(false, true)), Base64Module::new(true, true).process(&data).unwrap()),
+        (Box::new(RleModule::new(false)), RleModule::new(true).process(&data).unwrap()),
+    ];
+    for (module, encoded) in &decoders {
+        for chunk in [1, 3, 7, 1000] {
+            let mut stream = module.stream().expect("module streams");
+            assert_eq!(run_stream(stream.as_mut(), encoded, chunk), data, "{} chunk {}", module.name(), chunk);
+        }
+    }
+
+    let rle_decode = RleModule::new(false);
+    let mut truncated = rle_decode.stream().unwrap();
+    assert!(truncated.update(&[0x05, 1, 2]).unwrap().is_empty());
+    assert!(matches!(truncated.finish(), Err(ByteProcError::InvalidInput { .. })));
+}
+
+#[test]
+fn test_stream_hex_through_registry() {
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["xor".into(), "rle".into(), "reverse".into(), "base64".into()];
+    cfg.xor_key = Some("a5".into());
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+
+    let data: Vec<u8> = (0..5000u32).map(|i| (i / 40) as u8).collect();
+    let expected = format!("{}\n", hex::encode(registry.process_all(data.clone()).unwrap()));
+    // line breaks in the input are ignored, chunks split hex digit pairs
+    let input = hex::encode(&data).as_bytes().chunks(77).collect::<Vec<_>>().join(&b'\n');
+    let mut output = Vec::new();
+    stream_hex(&registry, 64 * 1024, 1, input.as_slice(), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+    // reverse is buffered and bounded by max_buffer
+    let result = stream_hex(&registry, 100, 16, input.as_slice(), Vec::new());
+    assert!(matches!(result, Err(ByteProcError::MaxSizeExceeded(100, _))));
+
+    let result = stream_hex(&registry, 100, 16, &b"abc"[..], Vec::new());
+    assert!(matches!(result, Err(ByteProcError::HexDecode(_))));
+}
+
+#[test]
+fn test_context_metadata_from_modules() {
+    let data = b"metadata".to_vec();
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["repetition".into(), "channel".into()];
+    cfg.repetition_count = 5;
+    cfg.channel_ber = 0.02;
+    let mut ctx = ProcessContext::new(7, "stdin");
+    let noisy = ModuleRegistry::new(&cfg).unwrap().process_all_with_context(data.clone(), &mut ctx).unwrap().output().unwrap();
+    let injected = ctx.get("channel.bit_errors").unwrap().to_string();
+    assert_ne!(injected, "0");
+
+    // the decoder reports every injected error as a corrected disagreement
+    cfg.pipeline = vec!["repetition".into()];
+    cfg.repetition_mode = RepetitionMode::Decode;
+    let decoded = ModuleRegistry::new(&cfg).unwrap().process_all_with_context(noisy, &mut ctx).unwrap().output().unwrap();
+    assert_eq!(decoded, data);
+    assert_eq!(ctx.get("repetition.corrected_bits"), Some(injected.as_str()));
+    assert_eq!(ctx.sequence, 7);
+}
+
+#[test]
+fn test_context_json_roundtrip() {
+    let mut ctx = ProcessContext::new(42, "tcp://127.0.0.1:5555");
+    ctx.set("deframer.frames", 3);
+    ctx.set("tag", "capture-1");
+    let json = ctx.to_json();
+    let back = ProcessContext::from_json(json.as_bytes()).unwrap();
+    assert_eq!(back, ctx);
+
+    // a downstream hop keeps sequence, creation time and metadata
+    let hop = ProcessContext::received(back, "tcp://127.0.0.1:5556");
+    assert_eq!(hop.sequence, 42);
+    assert_eq!(hop.created_us, ctx.created_us);
+    assert_eq!(hop.source, "tcp://127.0.0.1:5556");
+    assert_eq!(hop.get("deframer.frames"), Some("3"));
+
+    // missing fields default, junk is rejected
+    assert_eq!(ProcessContext::from_json(b"{}").unwrap(), ProcessContext::default());
+    assert!(ProcessContext::from_json(b"not json").is_err());
+}
+
+struct BrokenSink;
+
+impl Sink for BrokenSink {
+    fn name(&self) -> String {
+        "broken".into()
+    }
+
+    fn send(&mut self, _hex: &str, _ctx: &ProcessContext) -> Result<(), ByteProcError> {
+        Err(ByteProcError::Io("disk full".into()))
+    }
+}
+
+#[test]
+fn test_fan_out_sink_policies() {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b) = (dir.path().join("a.hex"), dir.path().join("b.hex"));
+    let ctx = ProcessContext::new(0, "stdin");
+
+    // a warn sink failing does not stop delivery to the others
+    let mut fan_out = FanOut::new();
+    fan_out.add(Box::new(FileSink::new(&a).unwrap()), SinkErrorPolicy::Fail);
+    fan_out.add(Box::new(BrokenSink), SinkErrorPolicy::Warn);
+    fan_out.add(Box::new(FileSink::new(&b).unwrap()), SinkErrorPolicy::Fail);
+    assert_eq!(fan_out.send("cafe", &ctx).unwrap(), 2);
+    assert_eq!(fan_out.send("babe", &ctx).unwrap(), 2);
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "cafe\nbabe\n");
+    assert_eq!(std::fs::read_to_string(&b).unwrap(), "cafe\nbabe\n");
+
+    let mut fan_out = FanOut::new();
+    fan_out.add(Box::new(BrokenSink), SinkErrorPolicy::Fail);
+    assert!(matches!(fan_out.send("cafe", &ctx), Err(ByteProcError::Io(_))));
+
+    // outputs come from the config file; without them output_type is used
+    let cfg: Config = serde_json::from_str(
+        r#"{"outputs": [{"type": "stdout"}, {"type": "zmq_push", "endpoint": "tcp://localhost:5555", "on_error": "warn"}]}"#,
+    ).unwrap();
+    assert!(cfg.validate().is_ok());
+    assert_eq!(cfg.output_specs()[1].on_error, SinkErrorPolicy::Warn);
+    let cfg: Config = serde_json::from_str(r#"{"outputs": [{"type": "file"}]}"#).unwrap();
+    assert!(matches!(cfg.validate(), Err(ByteProcError::InvalidConfiguration(_))));
+    let mut cfg = Config::default();
+    cfg.output_type = OutputType::File;
+    assert!(cfg.validate().is_err());
+    cfg.output_file = Some(a.clone());
+    assert_eq!(cfg.output_specs()[0].path, Some(a));
+}
+
+#[test]
+fn test_fan_in_merges_endpoints() {
+    let context = zmq::Context::new();
+    let inputs = vec![
+        InputSpec { endpoint: "inproc://fan-in-a".into(), bind: true },
+        InputSpec { endpoint: "inproc://fan-in-b".into(), bind: true },
+    ];
+    let mut fan_in = FanIn::open(&context, &inputs, &Config::default()).unwrap();
+    assert_eq!(fan_in.recv(0).unwrap(), None);
+
+    let push_a = context.socket(zmq::PUSH).unwrap();
+    push_a.connect("inproc://fan-in-a").unwrap();
+    let push_b = context.socket(zmq::PUSH).unwrap();
+    push_b.connect("inproc://fan-in-b").unwrap();
+    push_a.send("aa", 0).unwrap();
+    let upstream = ProcessContext::new(9, "stdin");
+    push_b.send("bb", zmq::SNDMORE).unwrap();
+    push_b.send(&upstream.to_json(), 0).unwrap();
+
+    let mut received = Vec::new();
+    while let Some(msg) = fan_in.recv(1000).unwrap() {
+        received.push(msg);
+        if received.len() == 2 {
+            break;
+        }
+    }
+    received.sort_by(|x, y| x.endpoint.cmp(&y.endpoint));
+    assert_eq!(received[0].hex, "aa");
+    assert_eq!(received[0].context, None);
+    assert_eq!(received[1].hex, "bb");
+    assert_eq!(received[1].endpoint, "inproc://fan-in-b");
+    assert_eq!(received[1].context, Some(upstream));
+}
+
+#[test]
+fn test_router_dispatches_by_predicate() {
+    let cfg: Config = serde_json::from_str(r#"{
+        "xor_key": "ff",
+        "pipelines": { "inverted": ["xor"], "reversed": ["reverse"] },
+        "routes": [
+            { "name": "magic", "when": { "prefix": "cafe" }, "pipeline": "inverted" },
+            { "when": { "all": [ { "length": { "min": 4 } }, { "regex": "^(..)*00" } ] }, "pipeline": "reversed" },
+            { "name": "tagged", "when": { "metadata": { "key": "tag", "value": "x" } }, "outputs": ["archive"] },
+            { "name": "bad_crc", "when": { "not": { "crc": { "kind": "crc16" } } }, "outputs": ["archive"] }
+        ],
+        "outputs": [ { "type": "stdout" }, { "name": "archive", "type": "file", "path": "./a.hex" } ]
+    }"#).unwrap();
+    cfg.validate().unwrap();
+    let router = Router::new(&cfg, &[]).unwrap();
+
+    let mut ctx = ProcessContext::default();
+    let (data, outputs) = router.dispatch(vec![0xca, 0xfe, 0x01], &mut ctx).unwrap();
+    assert_eq!(data, PipelineOutcome::Output(vec![0x35, 0x01, 0xfe]));
+    assert!(outputs.is_empty());
+    assert_eq!(ctx.get("router.route"), Some("magic"));
+
+    // the regex runs over whole hex bytes, so 0x10 0x0f does not count as "00"
+    let (data, _) = router.dispatch(vec![1, 0, 2, 3], &mut ctx).unwrap();
+    assert_eq!(data, PipelineOutcome::Output(vec![3, 2, 0, 1]));
+    assert_eq!(ctx.get("router.route"), Some("routes[1]"));
+
+    // 123456789 with its CRC-16/CCITT-FALSE matches no route
+    let good = b"123456789\x29\xb1".to_vec();
+    let mut ctx = ProcessContext::default();
+    let (data, outputs) = router.dispatch(good.clone(), &mut ctx).unwrap();
+    assert_eq!((data, outputs.len()), (PipelineOutcome::Output(good.clone()), 0));
+    assert_eq!(ctx.get("router.route"), None);
+
+    let mut bad = good.clone();
+    bad[0] ^= 1;
+    let (_, outputs) = router.dispatch(bad, &mut ctx).unwrap();
+    assert_eq!(outputs, ["archive".to_string()]);
+    assert_eq!(ctx.get("router.route"), Some("bad_crc"));
+
+    let mut ctx = ProcessContext::default();
+    ctx.set("tag", "x");
+    router.dispatch(good, &mut ctx).unwrap();
+    assert_eq!(ctx.get("router.route"), Some("tagged"));
+}
+
+#[test]
+fn test_router_config_errors() {
+    assert!(Predicate::Prefix("xyz".into()).compile().is_err());
+    assert!(Predicate::Regex("(".into()).compile().is_err());
+    assert!(Predicate::Length { min: Some(4), max: Some(2) }.compile().is_err());
+
+    let parse = |json: &str| serde_json::from_str::<Config>(json).unwrap().validate();
+    assert!(matches!(
+        parse(r#"{"routes": [{"when": {"prefix": "00"}, "pipeline": "missing"}]}"#),
+        Err(ByteProcError::InvalidConfiguration(msg)) if msg.contains("unknown pipeline missing")
+    ));
+    assert!(parse(r#"{"routes": [{"when": {"prefix": "00"}, "outputs": ["nowhere"]}]}"#).is_err());
+    assert!(parse(r#"{"routes": [{"when": {"crc": {"kind": "none"}}}]}"#).is_err());
+    assert!(parse(r#"{"routes": [{"when": {"any": [{"prefix": "00"}, {"length": {"max": 8}}]}}]}"#).is_ok());
+}
+
+fn invertible_config() -> Config {
+    let mut cfg = Config::default();
+    cfg.pipeline = ["xor", "pad", "framer", "bitops", "rle", "base64", "repetition", "bits", "symbols"]
+        .iter().map(|&s| s.into()).collect();
+    cfg.xor_key = Some("a5c3".into());
+    cfg.bitops_op = BitOp::Rotl;
+    cfg.bitops_amount = 3;
+    cfg.bits_per_byte = 4;
+    cfg
+}
+
+#[test]
+fn test_registry_inverse_roundtrip() {
+    let data = b"inverse pipelines, the easy way".to_vec();
+    let cfg = invertible_config();
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    let encoded = registry.process_all(data.clone()).unwrap();
+    assert_ne!(encoded, data);
+
+    // module-level inverse and the emitted inverse config agree
+    let inverse = registry.inverse().unwrap();
+    assert_eq!(inverse.process_all(encoded.clone()).unwrap(), data);
+    let inverted = ModuleRegistry::new(&cfg.inverted().unwrap()).unwrap();
+    assert_eq!(inverted.process_all(encoded).unwrap(), data);
+
+    // the inverse of the inverse encodes again
+    assert_eq!(
+        inverse.inverse().unwrap().process_all(data.clone()).unwrap(),
+        registry.process_all(data).unwrap()
+    );
+
+    let xor = XorModule::new("ff", None).unwrap();
+    let back = xor.as_invertible().unwrap().invert().unwrap();
+    assert_eq!(back.process(&xor.process(&[1, 2]).unwrap()).unwrap(), vec![1, 2]);
+    let b64 = Base64Module::new(true, true).as_invertible().unwrap().invert().unwrap();
+    assert_eq!(b64.process(b"AQI=").unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn test_inverted_config() {
+    let cfg = invertible_config();
+    let inv = cfg.inverted().unwrap();
+    assert_eq!(
+        inv.stage_names(),
+        ["symbols", "bits", "repetition", "base64", "rle", "bitops", "deframer", "pad", "xor"]
+    );
+    assert_eq!(inv.base64_mode, Base64Mode::Decode);
+    assert_eq!(inv.bitops_op, BitOp::Rotr);
+    assert_eq!(inv.pad_mode, PadMode::Unpad);
+    assert_eq!(inv.repetition_mode, RepetitionMode::Decode);
+
+    // the emitted JSON loads back as the same config
+    let json = serde_json::to_string(&inv).unwrap();
+    let loaded: Config = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.inverted().unwrap().pipeline, cfg.pipeline);
+
+    // stages without an inverse are rejected, also at validation
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["xor".into(), "channel".into()];
+    cfg.xor_key = Some("ff".into());
+    assert!(matches!(cfg.inverted(), Err(ByteProcError::InvalidConfiguration(msg)) if msg.contains("channel")));
+    cfg.invert_pipeline = InvertPipeline::Run;
+    assert!(cfg.validate().is_err());
+    cfg.pipeline = vec!["bitops".into()];
+    cfg.bitops_op = BitOp::And;
+    cfg.bitops_mask = Some("0f".into());
+    assert!(cfg.validate().is_err());
+    assert!(ModuleRegistry::new(&cfg).unwrap().inverse().is_err());
+
+    // emit and run share the sbox permutation check
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["sbox".into()];
+    cfg.sbox_table = Some("00".repeat(256));
+    assert!(cfg.inverted().is_err());
+    let table: Vec<u8> = (0..=255u8).map(|b| b.wrapping_mul(5).wrapping_add(3)).collect();
+    cfg.sbox_table = Some(hex::encode(&table));
+    let data: Vec<u8> = (0..=255u8).collect();
+    let encoded = ModuleRegistry::new(&cfg).unwrap().process_all(data.clone()).unwrap();
+    let emitted = ModuleRegistry::new(&cfg.inverted().unwrap()).unwrap();
+    assert_eq!(emitted.process_all(encoded).unwrap(), data);
+}
+
+#[test]
+fn test_error_policies_and_dead_letters() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut cfg = Config::default();
+    cfg.error_policy = ErrorPolicy::DeadLetter;
+    assert!(cfg.validate().is_err());
+    cfg.dead_letter_file = Some(dir.path().join("dead.jsonl"));
+    assert!(cfg.validate().is_ok());
+
+    let mut dead_letters = open_dead_letters(&zmq::Context::new(), &cfg).unwrap();
+    let mut ctx = ProcessContext::new(3, "tcp://127.0.0.1:5555");
+    ctx.set("tag", "x");
+    let err = || ByteProcError::HexDecode("Odd number of digits".into());
+
+    assert!(matches!(
+        handle_failed_message(ErrorPolicy::Abort, &mut dead_letters, "abc", &ctx, err()),
+        Err(ByteProcError::HexDecode(_))
+    ));
+    handle_failed_message(ErrorPolicy::Skip, &mut dead_letters, "abc", &ctx, err()).unwrap();
+    handle_failed_message(ErrorPolicy::DeadLetter, &mut dead_letters, "abc", &ctx, err()).unwrap();
+
+    // only the dead_letter call wrote a record, with everything needed to replay it
+    let written = std::fs::read_to_string(cfg.dead_letter_file.as_ref().unwrap()).unwrap();
+    assert_eq!(written.lines().count(), 1);
+    let letter: DeadLetter = serde_json::from_str(written.trim()).unwrap();
+    assert_eq!(letter.input, "abc");
+    assert_eq!(letter.error_kind, "hex_decode");
+    assert_eq!(letter.error, "Hex decode error: Odd number of digits");
+    assert_eq!(letter.context, ctx);
+
+    assert_eq!(ErrorPolicy::from_str("dead_letter").unwrap(), ErrorPolicy::DeadLetter);
+    cfg.stream = true;
+    assert!(cfg.validate().is_ok());
+    // a dead-letter target is only used by the dead_letter policy
+    cfg.error_policy = ErrorPolicy::Abort;
+    assert!(matches!(cfg.validate(), Err(ByteProcError::InvalidConfiguration(msg)) if msg.contains("dead_letter_file")));
+}
+
+#[test]
+fn test_stream_error_policies() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut cfg = Config::default();
+    cfg.error_policy = ErrorPolicy::DeadLetter;
+    cfg.dead_letter_file = Some(dir.path().join("dead.jsonl"));
+    let registry = ModuleRegistry::new(&cfg).unwrap();
+    let mut dead_letters = open_dead_letters(&zmq::Context::new(), &cfg).unwrap();
+
+    // 2-byte chunks are read as 4 hex characters: the bad chunk is dropped
+    let input = &b"0102zz030405\n"[..];
+    let mut output = Vec::new();
+    stream_hex_with_policy(&registry, 1024, 2, input, &mut output, ErrorPolicy::DeadLetter, &mut dead_letters).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "01020405\n");
+    let written = std::fs::read_to_string(cfg.dead_letter_file.as_ref().unwrap()).unwrap();
+    let letter: DeadLetter = serde_json::from_str(written.trim()).unwrap();
+    assert_eq!((letter.input.as_str(), letter.error_kind.as_str()), ("zz03", "hex_decode"));
+    assert_eq!(letter.context.get("stream.offset"), Some("4"));
+
+    let mut output = Vec::new();
+    stream_hex_with_policy(&registry, 1024, 2, input, &mut output, ErrorPolicy::Skip, &mut FanOut::new()).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "01020405\n");
+    let result = stream_hex_with_policy(&registry, 1024, 2, input, Vec::new(), ErrorPolicy::Abort, &mut FanOut::new());
+    assert!(matches!(result, Err(ByteProcError::HexDecode(_))));
+}
+
+#[test]
+fn test_stage_on_error_policies() {
+    let garbage = vec![0xde, 0xad];
+    let parse = |huffman: &str| -> Config {
+        serde_json::from_str(&format!(r#"{{
+            "pipeline": [{}, "xor"],
+            "huffman_mode": "decode",
+            "xor_key": "ff"
+        }}"#, huffman)).unwrap()
+    };
+    let cfg = parse(r#""huffman""#);
+    let mut ctx = ProcessContext::default();
+    assert!(ModuleRegistry::new(&cfg).unwrap().process_all_with_context(garbage.clone(), &mut ctx).is_err());
+
+    // passthrough forwards the failed stage's input to the next stage
+    let cfg = parse(r#"{"module": "huffman", "on_error": "passthrough"}"#);
+    assert_eq!(cfg.pipeline[0].on_error, StageErrorPolicy::Passthrough);
+    let out = ModuleRegistry::new(&cfg).unwrap().process_all_with_context(garbage.clone(), &mut ctx).unwrap();
+    assert_eq!(out, PipelineOutcome::Output(vec![0x21, 0x52]));
+    assert_eq!(ctx.get("pipeline[0].huffman.on_error"), Some("passthrough"));
+    assert!(ctx.get("pipeline[0].huffman.error").unwrap().starts_with("Invalid input at byte 2"));
+
+    // drop stops the chain, so xor never runs
+    let cfg = parse(r#"{"module": "huffman", "on_error": "drop"}"#);
+    let mut ctx = ProcessContext::default();
+    let out = ModuleRegistry::new(&cfg).unwrap().process_all_with_context(garbage.clone(), &mut ctx).unwrap();
+    assert_eq!(out, PipelineOutcome::Dropped { stage: "pipeline[0].huffman".into() });
+    assert!(ctx.metadata.keys().all(|key| !key.contains("dropped")));
+    cfg.validate().unwrap();
+
+    // a repeated module keeps its own policy and its own metadata
+    let mut cfg = parse(r#"{"module": "huffman", "on_error": "passthrough"}"#);
+    cfg.pipeline.push(StageSpec { module: "huffman".into(), on_error: StageErrorPolicy::Passthrough });
+    let mut ctx = ProcessContext::default();
+    ModuleRegistry::new(&cfg).unwrap().process_all_with_context(garbage, &mut ctx).unwrap();
+    assert!(ctx.get("pipeline[0].huffman.error").is_some());
+    assert!(ctx.get("pipeline[2].huffman.error").is_some());
+
+    // plain names serialize back as plain names
+    let json = serde_json::to_value(&cfg.pipeline).unwrap();
+    assert_eq!(json[1], "xor");
+    assert_eq!(json[2]["on_error"], "passthrough");
+    cfg.stream = true;
+    assert!(cfg.validate().is_err());
+}
+
+#[test]
+fn test_stage_errors_and_exit_codes() {
+    use std::error::Error;
+
+    let mut cfg = Config::default();
+    cfg.pipeline = vec!["xor".into(), "rle".into()];
+    cfg.xor_key = Some("00".into());
+    cfg.rle_mode = RleMode::Decode;
+    let err = ModuleRegistry::new(&cfg).unwrap().process_all(vec![0x00, 0x07, 0x05, 0x01]).unwrap_err();
+    assert_eq!(err.stage(), Some("rle"));
+    assert_eq!(err.offset(), Some(2));
+    assert_eq!(err.kind(), "invalid_input");
+    assert_eq!(err.exit_code(), 65);
+    assert_eq!(err.to_string(), "Stage rle failed: Invalid input at byte 2: rle literal run is truncated");
+    let source = err.source().unwrap().downcast_ref::<ByteProcError>().unwrap();
+    assert!(matches!(source, ByteProcError::InvalidInput { offset: 2, .. }));
+
+    assert_eq!(ByteProcError::InvalidConfiguration("x".into()).exit_code(), 78);
+    assert_eq!(ByteProcError::Io("x".into()).exit_code(), 74);
+    assert_eq!(ByteProcError::Zmq("x".into()).exit_code(), 69);
+    assert_eq!(ByteProcError::Module("x".into()).exit_code(), 70);
+    assert_eq!(ByteProcError::HexDecode("x".into()).exit_code(), 65);
+    assert_eq!(ByteProcError::MaxSizeExceeded(1, 2).exit_code(), 65);
+}
+
+#[test]
+fn test_error_report_fields() {
+    let err = ByteProcError::Stage {
+        stage: "rle".into(),
+        source: Box::new(ByteProcError::InvalidInput { offset: 2, message: "rle literal run is truncated".into() }),
+    };
+    let input = InputSummary::new(&"ab".repeat(40), &ProcessContext::new(5, "tcp://127.0.0.1:5555"));
+    assert_eq!(input.hex_chars, 80);
+    assert_eq!(input.head.len(), 32);
+
+    let report = ErrorReport::new(&err, Some(input.clone()));
+    assert_eq!(report.kind, "invalid_input");
+    assert_eq!(report.module.as_deref(), Some("rle"));
+    assert_eq!(report.offset, Some(2));
+    assert_eq!(report.exit_code, 65);
+    let back: ErrorReport = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(back.input, Some(input.clone()));
+
+    let other = std::io::Error::other("boom");
+    let report = ErrorReport::new(&other, None);
+    assert_eq!((report.kind.as_str(), report.exit_code, report.module), ("other", 1, None));
+
+    // the input travels with the error
+    let failed: Box<dyn std::error::Error> = Box::new(MessageError { input: input.clone(), error: err });
+    assert!(failed.to_string().starts_with("Stage rle failed"));
+    let report = ErrorReport::from_error(failed.as_ref());
+    assert_eq!((report.kind.as_str(), report.offset), ("invalid_input", Some(2)));
+    assert_eq!(report.input, Some(input));
+    assert_eq!(ErrorReport::from_error(&other).input, None);
+}
+
+#[test]
+fn test_binary_json_error_on_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_byteproc"))
+        .args(["--error-format", "json", "--pipeline", "rle", "--rle-mode", "decode", "--log-file"])
+        .arg(dir.path().join("byteproc.log"))
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"0007050102\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    let report: ErrorReport = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report.module.as_deref(), Some("rle"));
+    assert_eq!(report.offset, Some(2));
+    let input = report.input.unwrap();
+    assert_eq!((input.source.as_str(), input.head.as_str()), ("stdin", "0007050102"));
+}
+
+#[test]
+fn test_iso8601_utc() {
+    assert_eq!(iso8601_utc(0), "1970-01-01T00:00:00.000000Z");
+    assert_eq!(iso8601_utc(951_782_400_000_001), "2000-02-29T00:00:00.000001Z");
+    assert_eq!(iso8601_utc(1_714_564_800_123_456), "2024-05-01T12:00:00.123456Z");
+}
+
+#[test]
+fn test_binary_structured_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("byteproc.log");
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_byteproc"))
+        .args(["--log-structured", "--pipeline", "reverse", "--log-file"])
+        .arg(&log_file)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id();
+    child.stdin.take().unwrap().write_all(b"010203\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "030201");
+
+    let records: Vec<LogRecord> = std::fs::read_to_string(&log_file).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(records.iter().all(|r| r.pid == pid && r.instance_id.starts_with("pid-")));
+    assert!(records.iter().all(|r| r.timestamp.ends_with('Z') && !r.message.starts_with('[')));
+    let done = records.iter().find(|r| r.message == "Module reverse done").unwrap();
+    assert_eq!(done.level, "INFO");
+    assert_eq!(done.stage.as_deref(), Some("reverse"));
+    // Stdin messages are numbered from the instance start time
+    let message_id = done.message_id.unwrap();
+    assert!(message_id > 1_600_000_000_000_000);
+    assert!(records.iter().filter(|r| r.message_id.is_some()).all(|r| r.message_id == Some(message_id)));
+    assert_eq!(done.fields["bytes_in"], 3);
+    assert_eq!(done.fields["bytes_out"], 3);
+}
+
+#[test]
+fn test_binary_structured_log_stage_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("byteproc.log");
+    let config = dir.path().join("config.json");
+    std::fs::write(&config, r#"{
+        "pipeline": [{ "module": "huffman", "on_error": "passthrough" }],
+        "huffman_mode": "decode"
+    }"#).unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_byteproc"))
+        .args(["--log-structured", "--config"])
+        .arg(&config)
+        .arg("--log-file")
+        .arg(&log_file)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"dead\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "dead");
+
+    // the on_error warning is logged while the stage is still set
+    let records: Vec<LogRecord> = std::fs::read_to_string(&log_file).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let failed = records.iter().find(|r| r.message.starts_with("Stage pipeline[0].huffman failed")).unwrap();
+    assert_eq!((failed.level.as_str(), failed.stage.as_deref()), ("WARN", Some("huffman")));
+    let delivered = records.iter().find(|r| r.message.ends_with("delivered")).unwrap();
+    assert_eq!(delivered.stage, None);
+}
+
+#[test]
+fn test_log_rotation_shared_between_writers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("byteproc.log");
+    let mut a = RotatingLogFile::open(&path, 100, 3, true).unwrap();
+    let mut b = RotatingLogFile::open(&path, 100, 3, true).unwrap();
+    for i in 0..20 {
+        // Partial writes are held back until the line is complete
+        write!(a, "a{:02} ", i).unwrap();
+        writeln!(b, "b{:02} 0123456789", i).unwrap();
+        writeln!(a, "0123456789").unwrap();
+    }
+    drop((a, b));
+
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    assert!(!dir.path().join("byteproc.log.4").exists());
+    let files = [read("byteproc.log.3"), read("byteproc.log.2"), read("byteproc.log.1"), read("byteproc.log")];
+    for content in &files {
+        assert!(content.len() <= 100);
+        assert!(content.lines().all(|line| line.len() == 14));
+    }
+    // The newest lines survive, in order, in the newest files
+    let lines: Vec<&str> = files.iter().flat_map(|c| c.lines()).collect();
+    assert_eq!(lines.last(), Some(&"a19 0123456789"));
+    // 15 bytes per line, 6 per file: 40 lines leave 4 in the current file
+    assert_eq!(lines.len(), 3 * 6 + 4);
+
+    let truncated = RotatingLogFile::open(&path, 100, 3, false).unwrap();
+    drop(truncated);
+    assert!(read("byteproc.log").is_empty());
+}
+
+#[test]
+fn test_log_collector_merges_instances() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("logs.ipc");
+    let endpoint = format!("ipc://{}", socket_path.display());
+    let collector = std::process::Command::new(env!("CARGO_BIN_EXE_byteproc"))
+        .args(["--log-zmq-socket", &endpoint, "--log-file"])
+        .arg(dir.path().join("collector.log"))
+        .args(["log-collector", "--json", "--window-ms", "200", "--idle-ms", "1500"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The ipc socket file appears once the collector has bound the endpoint
+    let bound = Instant::now();
+    while !socket_path.exists() {
+        assert!(bound.elapsed() < Duration::from_secs(10), "collector did not bind {}", endpoint);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut pids = Vec::new();
+    for name in ["a", "b"] {
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_byteproc"))
+            .args(["--pipeline", "reverse", "--log-zmq-socket", &endpoint, "--log-file"])
+            .arg(dir.path().join(format!("{}.log", name)))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        pids.push(child.id());
+        child.stdin.take().unwrap().write_all(b"0102\n").unwrap();
+        assert!(child.wait_with_output().unwrap().status.success());
+    }
+
+    let output = collector.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let records: Vec<LogRecord> = String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(records.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    for pid in pids {
+        let own: Vec<&LogRecord> = records.iter().filter(|r| r.pid == pid).collect();
+        assert_eq!(own[0].message, "Byteproc starting up");
+        assert!(own.iter().all(|r| r.instance_id == own[0].instance_id));
+        assert!(own.iter().any(|r| r.message == "Module reverse done"));
+    }
+}
+
+#[test]
+fn test_log_collector_command_line() {
+    let cfg = Config::try_parse_from(["byteproc", "--log-zmq-socket", "tcp://*:5600", "log-collector", "--file", "all.log"]).unwrap();
+    let Some(Command::LogCollector(args)) = &cfg.command else { panic!("no log-collector command") };
+    assert_eq!(args, &LogCollectorArgs { file: Some("all.log".into()), ..Default::default() });
+    cfg.validate().unwrap();
+
+    let cfg = Config::try_parse_from(["byteproc", "log-collector"]).unwrap();
+    assert!(matches!(cfg.validate(), Err(ByteProcError::InvalidConfiguration(msg)) if msg.contains("log_zmq_socket")));
+    // processing options keep their meaning and are not collector options
+    assert!(Config::try_parse_from(["byteproc", "log-collector", "--output-type", "file"]).is_err());
+    assert!(Config::try_parse_from(["byteproc"]).unwrap().command.is_none());
+}